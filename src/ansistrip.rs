@@ -0,0 +1,55 @@
+// Removes ANSI escape sequences (colors, cursor movement, titles) from streamed text.
+// A sequence can be split across chunks, so the parser state carries over between pushes.
+#[derive(Default)]
+pub struct AnsiStripper {
+    state: State,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum State {
+    #[default]
+    Text,
+    // Just saw ESC
+    Escape,
+    // Inside a CSI sequence (ESC [ ... final byte), e.g. colors
+    Csi,
+    // Inside an OSC sequence (ESC ] ... BEL or ESC \), e.g. window titles
+    Osc,
+    // Saw ESC inside an OSC sequence, expecting the closing backslash
+    OscEscape,
+}
+
+const ESC: char = '\u{1b}';
+const BEL: char = '\u{07}';
+const CSI: char = '\u{9b}';
+
+impl AnsiStripper {
+    // Function to feed a chunk of streamed text and get it back without escape sequences
+    pub fn push(&mut self, chunk: &str) -> String {
+        let mut output = String::with_capacity(chunk.len());
+
+        for c in chunk.chars() {
+            self.state = match (self.state, c) {
+                (State::Text, ESC) => State::Escape,
+                (State::Text, CSI) => State::Csi,
+                (State::Text, c) => {
+                    output.push(c);
+                    State::Text
+                },
+                (State::Escape, '[') => State::Csi,
+                (State::Escape, ']') => State::Osc,
+                // Other escapes are two characters long, e.g. ESC c (reset)
+                (State::Escape, _) => State::Text,
+                // Parameter and intermediate bytes keep the sequence going until the final byte
+                (State::Csi, '\u{20}'..='\u{3f}') => State::Csi,
+                (State::Csi, _) => State::Text,
+                (State::Osc, BEL) => State::Text,
+                (State::Osc, ESC) => State::OscEscape,
+                (State::Osc, _) => State::Osc,
+                (State::OscEscape, _) => State::Text,
+            };
+        }
+
+        output
+    }
+}
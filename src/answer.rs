@@ -0,0 +1,43 @@
+use ollama_rs::generation::completion::request::GenerationRequest;
+
+use crate::error;
+use crate::metrics::Metrics;
+use crate::{client, critique, logging, options, params, prompt, settings, streaming};
+
+// Function to answer one prompt on the local instance or the remote server, shared by the
+// menu's generate options and --prompt. `interactive` is set when someone typed the prompt
+// at the menu: a missing model may then be pulled, and the full metrics are printed.
+pub async fn answer(user_prompt: String, local: bool, interactive: bool) -> Result<Metrics, Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+
+    let user_prompt = prompt::prepare(user_prompt);
+    let request_id = logging::start_request(&user_prompt);
+    let model = settings::model_for(local);
+
+    let (ollama, server_url) = client::build_client(local)?;
+    logging::info(&format!("Connecting to: {}", server_url));
+    logging::info(&format!("Using model: {}", model));
+
+    prompt::check_context(&ollama, &model, &user_prompt).await;
+    let request = options::apply(GenerationRequest::new(model.clone(), user_prompt), &model);
+
+    logging::info("");
+    logging::info("--- Response ---");
+
+    let metrics = streaming::generate(&ollama, local, request, &model, &request_id, interactive).await
+        .map_err(|e| error::explain(e, &server_url))?;
+
+    // Prompt mode only prints metrics when a format was requested, e.g. for dashboards
+    match settings::get().metrics_format.as_deref() {
+        format if interactive => metrics.print(format),
+        Some(format) => metrics.print(Some(format)),
+        None => {
+            logging::info("");
+            metrics.print_timing_bar();
+        },
+    }
+    params::show_effective(local, &model).await;
+    critique::self_critique(&ollama, &model, &metrics).await;
+
+    Ok(metrics)
+}
@@ -0,0 +1,63 @@
+use ollama_rs::generation::completion::request::GenerationRequest;
+use std::io::IsTerminal;
+
+use crate::connecttoollama::get_user_input;
+use crate::metrics::Metrics;
+use crate::{client, error, export, imagedescriber, logging, options, prompt, settings, status, streaming};
+
+// Function to ask which candidate --output, --clipboard and the post-hook should get.
+// Scripts, and runs that don't use the answer, keep the first one.
+fn choose_candidate(count: usize) -> usize {
+    if count < 2 || !export::wants_response() || !std::io::stdin().is_terminal() {
+        return 0;
+    }
+
+    loop {
+        let Some(selection) = get_user_input(&format!("Keep which candidate? (1-{}, Enter for 1) ", count)) else {
+            return 0;
+        };
+        if selection.is_empty() {
+            return 0;
+        }
+        match imagedescriber::parse_selection(&selection, count) {
+            Ok(index) => return index,
+            Err(e) => println!("{} {}", status::err(), e),
+        }
+    }
+}
+
+// Function to generate several alternative answers to one prompt. Ollama has no `n`
+// parameter, so each candidate is its own request with a different seed (1, 2, ...),
+// which also makes a favourite reproducible. Returns the candidate chosen to keep.
+pub async fn generate_candidates(prompt: String, count: u32, local: bool) -> Result<Metrics, Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+
+    let prompt = prompt::prepare(prompt);
+    let model = settings::model_for(local);
+    let (ollama, server_url) = client::build_client(local)?;
+    logging::info(&format!("Connecting to: {}", server_url));
+    logging::info(&format!("Using model: {}", model));
+
+    let mut results = Vec::new();
+
+    for index in 1..=count {
+        let seed = index as i32;
+        logging::info(&format!("\n=== Candidate {}/{} (seed {}) ===", index, count, seed));
+
+        let request_id = logging::start_request(&prompt);
+        let request = options::apply_seeded(GenerationRequest::new(model.clone(), prompt.clone()), &model, Some(seed));
+
+        let metrics = streaming::generate(&ollama, local, request, &model, &request_id, false).await
+            .map_err(|e| error::explain(e, &server_url))?;
+        logging::info("");
+        results.push((index, seed, metrics));
+    }
+
+    logging::info("\n=== Candidate Metrics ===");
+    for (index, seed, metrics) in &results {
+        logging::info(&format!("{} Candidate {} (seed {}): {} tokens in {:.2}s ({:.1} tok/s)", status::ok(), index, seed, metrics.tokens, metrics.total_time, metrics.tps));
+    }
+
+    let chosen = choose_candidate(results.len());
+    results.into_iter().nth(chosen).map(|(_, _, metrics)| metrics).ok_or_else(|| "No candidates were generated".into())
+}
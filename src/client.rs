@@ -0,0 +1,439 @@
+use ollama_rs::Ollama;
+use reqwest::Url;
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{error, settings, status};
+
+// Which server to use and whether to fall back to the other one.
+// Set with --policy or connection_policy in .env; remote-first is the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ConnectionPolicy {
+    #[default]
+    RemoteFirst,
+    LocalFirst,
+    RemoteOnly,
+    LocalOnly,
+}
+
+impl ConnectionPolicy {
+    // Function to get the policy's name as written in .env and on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConnectionPolicy::RemoteFirst => "remote-first",
+            ConnectionPolicy::LocalFirst => "local-first",
+            ConnectionPolicy::RemoteOnly => "remote-only",
+            ConnectionPolicy::LocalOnly => "local-only",
+        }
+    }
+}
+
+// Function to read connection_policy from .env. Called once at startup, before the settings
+// exist, so an unknown value is returned as a warning for the caller to print afterwards.
+// --policy takes precedence.
+pub fn policy_from_env() -> Result<Option<ConnectionPolicy>, String> {
+    settings::load_dotenv();
+
+    let Ok(value) = settings::env_var("connection_policy") else {
+        return Ok(None);
+    };
+    <ConnectionPolicy as clap::ValueEnum>::from_str(&value, true)
+        .map(Some)
+        .map_err(|_| format!("Unknown connection_policy '{}' in .env, using remote-first", value))
+}
+
+// Function to get the connection policy chosen at startup
+pub fn connection_policy() -> ConnectionPolicy {
+    settings::get().policy.unwrap_or_default()
+}
+
+// Function to list the connections to try in order (true = local) for the current policy.
+// The remote server only counts as an option when one is configured.
+pub fn connection_order() -> Vec<bool> {
+    let remote_configured = remote_configured();
+
+    match connection_policy() {
+        ConnectionPolicy::RemoteFirst if remote_configured => vec![false, true],
+        ConnectionPolicy::LocalFirst if remote_configured => vec![true, false],
+        ConnectionPolicy::RemoteFirst | ConnectionPolicy::LocalFirst => vec![true],
+        ConnectionPolicy::RemoteOnly => vec![false],
+        ConnectionPolicy::LocalOnly => vec![true],
+    }
+}
+
+// The server to talk to: its base URL (port included, path ending in "/") and auth header
+#[derive(Clone, PartialEq, Eq)]
+struct Target {
+    base_url: Url,
+    auth: Option<String>,
+}
+
+impl Target {
+    // Function to get the base URL without the trailing slash, e.g. for display or to append /api/...
+    fn display_url(&self) -> String {
+        self.base_url.as_str().trim_end_matches('/').to_string()
+    }
+}
+
+// Function to work out which server to talk to.
+// Remote settings come from --host/--port, then the selected profile, then .env.
+fn resolve_target(local: bool) -> Result<Target, Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+
+    if local {
+        return Ok(Target { base_url: Url::parse("http://localhost:11434/")?, auth: None });
+    }
+
+    let settings = settings::get();
+    let profile = settings.profile.clone().unwrap_or_default();
+
+    let server_ip = match settings.host.clone().or(profile.host) {
+        Some(host) => host,
+        None => settings::require_env("server_ip")?,
+    };
+    let https = settings.https || profile.https.unwrap_or(false);
+    let base_url = parse_server_url(&server_ip, https, settings.port.or(profile.port))?;
+
+    Ok(Target { base_url, auth: profile.auth })
+}
+
+// Function to find the port an address asks for. A URL gets its scheme's port unless it names
+// another one (http://proxy/ollama/ is port 80); a bare host only has one if it is written out.
+fn address_port(server_ip: &str) -> Option<u16> {
+    if server_ip.contains("://") {
+        Url::parse(server_ip).ok()?.port_or_known_default()
+    } else {
+        // A scheme without a default port keeps even :80 or :443 as written
+        Url::parse(&format!("ollama://{}", server_ip)).ok()?.port()
+    }
+}
+
+// Function to turn a server address into a base URL. Accepts a bare host ("192.168.1.50"),
+// host:port, or a full URL with a path for proxies that mount Ollama under a subpath
+// ("https://example.com/ollama/"). A scheme or port in the address wins over --https and
+// Ollama's default port; an explicit --port or profile port wins over both.
+fn parse_server_url(server_ip: &str, https: bool, port: Option<u16>) -> Result<Url, Box<dyn std::error::Error>> {
+    let server_ip = server_ip.trim();
+    let address = if server_ip.contains("://") {
+        server_ip.to_string()
+    } else {
+        format!("{}://{}", if https { "https" } else { "http" }, server_ip)
+    };
+
+    let mut url = Url::parse(&address).map_err(|e| format!("Invalid server address '{}': {}", server_ip, e))?;
+    let port = port.or(address_port(server_ip)).unwrap_or(default_port(url.scheme() == "https"));
+    url.set_port(Some(port)).map_err(|_| format!("Invalid server address '{}'", server_ip))?;
+
+    // ollama-rs appends "api/..." to the base URL, so the path must end in exactly one slash
+    let path = format!("{}/", url.path().trim_end_matches('/'));
+    url.set_path(&path);
+    url.set_query(None);
+    url.set_fragment(None);
+
+    Ok(url)
+}
+
+// A --connect "model@host:port" shorthand, split into its parts
+#[derive(Debug, Clone)]
+pub struct ConnectTarget {
+    pub model: String,
+    // The host in any form parse_server_url accepts
+    pub host: String,
+    // Kept apart from the host so it outranks a profile's port, like --port does
+    pub port: Option<u16>,
+}
+
+// Function to parse a --connect value while parsing arguments. The last @ separates the model
+// from the server, so tagged and registry names like hf.co/user/model:Q4_K_M work.
+pub fn parse_connect(raw: &str) -> Result<ConnectTarget, String> {
+    const USAGE: &str = "expected MODEL@HOST[:PORT], e.g. mistral@192.168.1.5:11434";
+
+    let (model, host) = raw.trim().rsplit_once('@').ok_or_else(|| format!("missing '@' in '{}': {}", raw, USAGE))?;
+    let (model, host) = (model.trim(), host.trim());
+    if model.is_empty() {
+        return Err(format!("no model before '@' in '{}': {}", raw, USAGE));
+    }
+    if host.is_empty() {
+        return Err(format!("no server after '@' in '{}': {}", raw, USAGE));
+    }
+    parse_server_url(host, false, None).map_err(|e| e.to_string())?;
+
+    Ok(ConnectTarget { model: model.to_string(), host: host.to_string(), port: address_port(host) })
+}
+
+// Function to pick the port when none is given: 443 behind an https proxy, Ollama's 11434 otherwise
+pub fn default_port(https: bool) -> u16 {
+    if https { 443 } else { 11434 }
+}
+
+// Clients built so far, one per target. Both Ollama and reqwest::Client share their
+// connection pool between clones, so handing out clones keeps connections alive
+// across menu iterations and interactive turns.
+struct CachedClient {
+    target: Target,
+    http_client: reqwest::Client,
+}
+
+static CLIENTS: Mutex<Vec<CachedClient>> = Mutex::new(Vec::new());
+
+// Function to get the shared HTTP client for a target, building it on first use
+// or when the connection settings for that target have changed
+fn shared_http_client(target: &Target) -> Result<reqwest::Client, Box<dyn std::error::Error>> {
+    let mut clients = CLIENTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if let Some(cached) = clients.iter().find(|cached| &cached.target == target) {
+        return Ok(cached.http_client.clone());
+    }
+
+    let http_client = reqwest::Client::builder()
+        .default_headers(request_headers(target.auth.as_deref())?)
+        .build()?;
+
+    // Drop the stale client for the same server before caching the new one
+    clients.retain(|cached| cached.target.base_url != target.base_url);
+    clients.push(CachedClient { target: target.clone(), http_client: http_client.clone() });
+    Ok(http_client)
+}
+
+// Function to get a client for the local instance or the configured remote server.
+// Returns the client together with the URL for display purposes. The client is
+// built once per target and reused, so callers should not hold on to their own.
+pub fn build_client(local: bool) -> Result<(Ollama, String), Box<dyn std::error::Error>> {
+    check_policy(local)?;
+    let target = resolve_target(local)?;
+    let http_client = shared_http_client(&target)?;
+
+    let port = target.base_url.port_or_known_default().unwrap_or(default_port(false));
+    let ollama = Ollama::new_with_client(target.base_url.clone(), port, http_client);
+    Ok((ollama, target.display_url()))
+}
+
+// Function to refuse connections the policy rules out, e.g. local under remote-only
+fn check_policy(local: bool) -> Result<(), Box<dyn std::error::Error>> {
+    match (connection_policy(), local) {
+        (ConnectionPolicy::RemoteOnly, true) => Err("connection_policy is remote-only; the local instance is disabled".into()),
+        (ConnectionPolicy::LocalOnly, false) => Err("connection_policy is local-only; the remote server is disabled".into()),
+        _ => Ok(()),
+    }
+}
+
+// Function to build a plain HTTP client for endpoints ollama-rs doesn't cover.
+// Returns the client and the server's base URL, e.g. http://localhost:11434
+pub fn build_http_client(local: bool) -> Result<(reqwest::Client, String), Box<dyn std::error::Error>> {
+    check_policy(local)?;
+    let target = resolve_target(local)?;
+
+    let http_client = shared_http_client(&target)?;
+    Ok((http_client, target.display_url()))
+}
+
+// Function to get the shared HTTP client for fetching a URL outside the Ollama API, e.g. an
+// image for --image. --header values are always sent; the profile's auth only when the URL
+// is on one of the configured servers, so credentials don't leak to other hosts.
+pub fn http_client_for_url(url: &Url) -> Result<reqwest::Client, Box<dyn std::error::Error>> {
+    let server = connection_order().into_iter()
+        .filter_map(|local| resolve_target(local).ok())
+        .find(|target| target.base_url.origin() == url.origin());
+
+    match server {
+        Some(target) => shared_http_client(&target),
+        None => shared_http_client(&Target { base_url: url.join("/")?, auth: None }),
+    }
+}
+
+// Function to collect the headers sent with every request: the profile's auth plus --header values
+fn request_headers(auth: Option<&str>) -> Result<HeaderMap, Box<dyn std::error::Error>> {
+    let mut headers = HeaderMap::new();
+
+    if let Some(auth) = auth {
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(auth)?);
+    }
+
+    // --header values were validated when the arguments were parsed
+    for (name, value) in &settings::get().headers {
+        headers.insert(HeaderName::from_bytes(name.as_bytes())?, HeaderValue::from_str(value)?);
+    }
+
+    Ok(headers)
+}
+
+// Function to parse a "Key: Value" header from the command line
+pub fn parse_header(raw: &str) -> Result<(String, String), String> {
+    let (name, value) = raw
+        .split_once(':')
+        .ok_or_else(|| format!("invalid header '{}': expected \"Key: Value\"", raw))?;
+    let name = name.trim();
+    let value = value.trim();
+
+    HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| format!("invalid header name '{}'", name))?;
+    HeaderValue::from_str(value)
+        .map_err(|_| format!("invalid value for header '{}'", name))?;
+
+    Ok((name.to_string(), value.to_string()))
+}
+
+// Function to decide the default target for menu actions: the first connection the policy allows
+pub fn prefer_local() -> bool {
+    connection_order()[0]
+}
+
+// Function to check whether a remote server is set via --host, the profile or server_ip
+fn remote_configured() -> bool {
+    settings::load_dotenv();
+
+    let settings = settings::get();
+    let profile_host = settings.profile.as_ref().and_then(|profile| profile.host.as_ref());
+    settings.host.is_some() || profile_host.is_some() || settings::env_var("server_ip").is_ok()
+}
+
+// How often --wait-for-server checks again
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Function to poll a server until it answers or --wait-for-server runs out, printing a dot per try.
+// For startup scripts that launch `ollama serve` and test straight away. Progress goes to stderr
+// so JSON output stays clean. Returns whether the server answered (always true without the flag).
+pub async fn wait_for_server(local: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let Some(seconds) = settings::get().wait_for_server else {
+        return Ok(true);
+    };
+
+    let (http_client, base_url) = build_http_client(local)?;
+    let deadline = Instant::now() + Duration::from_secs(seconds);
+    eprint!("Waiting for {} ", base_url);
+
+    loop {
+        // Any HTTP answer, even an error status, means the server is up
+        let answered = http_client
+            .get(format!("{}/api/version", base_url))
+            .timeout(Duration::from_secs(2))
+            .send()
+            .await
+            .is_ok();
+
+        if answered {
+            eprintln!(" {} up", status::ok());
+            return Ok(true);
+        }
+        if Instant::now() >= deadline {
+            eprintln!(" {} not reachable after {}s", status::err(), seconds);
+            return Ok(false);
+        }
+
+        eprint!(".");
+        let _ = std::io::stderr().flush();
+        tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+    }
+}
+
+// Function to probe a server once for --healthcheck: the request must come back with a success
+// status within `timeout`. Prints nothing; the caller reports failures and sets the exit code.
+pub async fn healthcheck(local: bool, timeout: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    let (http_client, base_url) = build_http_client(local)?;
+    let response = http_client
+        .get(format!("{}/api/version", base_url))
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| error::explain(e.into(), &base_url))?;
+    error::check_response(response).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_url(server_ip: &str, https: bool, port: Option<u16>) -> String {
+        parse_server_url(server_ip, https, port).unwrap().to_string()
+    }
+
+    #[test]
+    fn default_port_depends_on_https() {
+        assert_eq!(default_port(false), 11434);
+        assert_eq!(default_port(true), 443);
+    }
+
+    #[test]
+    fn implicit_port_uses_the_default_for_the_scheme() {
+        assert_eq!(server_url("192.168.1.50", false, None), "http://192.168.1.50:11434/");
+        assert_eq!(server_url("192.168.1.50", true, None), "https://192.168.1.50/");
+        assert_eq!(server_url("https://example.com", false, None), "https://example.com/");
+    }
+
+    #[test]
+    fn url_without_a_port_uses_the_port_of_its_scheme() {
+        assert_eq!(server_url("http://192.168.1.50", true, None), "http://192.168.1.50/");
+        assert_eq!(server_url("http://proxy:80/ollama", false, None), "http://proxy/ollama/");
+        assert_eq!(server_url("proxy:80", false, None), "http://proxy/");
+    }
+
+    #[test]
+    fn port_in_the_address_is_kept() {
+        assert_eq!(server_url("192.168.1.50:8080", false, None), "http://192.168.1.50:8080/");
+        assert_eq!(server_url("example.com:8443", true, None), "https://example.com:8443/");
+        assert_eq!(server_url("http://192.168.1.50:8080", true, None), "http://192.168.1.50:8080/");
+        assert_eq!(server_url("https://example.com:8443", false, None), "https://example.com:8443/");
+    }
+
+    #[test]
+    fn explicit_port_wins_over_the_address() {
+        assert_eq!(server_url("192.168.1.50", false, Some(9000)), "http://192.168.1.50:9000/");
+        assert_eq!(server_url("192.168.1.50", true, Some(9000)), "https://192.168.1.50:9000/");
+        assert_eq!(server_url("192.168.1.50:8080", false, Some(9000)), "http://192.168.1.50:9000/");
+        assert_eq!(server_url("https://example.com:8443", false, Some(9000)), "https://example.com:9000/");
+    }
+
+    #[test]
+    fn subpath_ends_in_exactly_one_slash() {
+        assert_eq!(server_url("http://h/ollama/", false, None), "http://h/ollama/");
+        assert_eq!(server_url("http://h/ollama", false, None), "http://h/ollama/");
+        assert_eq!(server_url("https://example.com/proxy/ollama//", false, None), "https://example.com/proxy/ollama/");
+        assert_eq!(server_url("http://h/", false, None), "http://h/");
+    }
+
+    #[test]
+    fn query_and_fragment_are_dropped() {
+        assert_eq!(server_url("http://h/ollama/?key=1#top", false, None), "http://h/ollama/");
+    }
+
+    #[test]
+    fn invalid_address_is_rejected() {
+        assert!(parse_server_url("http://", false, None).is_err());
+    }
+
+    #[test]
+    fn connect_splits_model_host_and_port() {
+        let target = parse_connect("mistral@192.168.1.5:11434").unwrap();
+        assert_eq!(target.model, "mistral");
+        assert_eq!(target.host, "192.168.1.5:11434");
+        assert_eq!(target.port, Some(11434));
+
+        let target = parse_connect("llama3.2@gpu-box").unwrap();
+        assert_eq!(target.host, "gpu-box");
+        assert_eq!(target.port, None);
+    }
+
+    #[test]
+    fn connect_keeps_a_default_port() {
+        assert_eq!(parse_connect("mistral@http://h:80").unwrap().port, Some(80));
+        assert_eq!(parse_connect("mistral@https://h/ollama/").unwrap().port, Some(443));
+        assert_eq!(parse_connect("mistral@h:80").unwrap().port, Some(80));
+    }
+
+    #[test]
+    fn connect_splits_on_the_last_at_sign() {
+        let target = parse_connect("hf.co/user/model:Q4_K_M@https://example.com/ollama/").unwrap();
+        assert_eq!(target.model, "hf.co/user/model:Q4_K_M");
+        assert_eq!(target.host, "https://example.com/ollama/");
+    }
+
+    #[test]
+    fn connect_rejects_incomplete_values() {
+        assert!(parse_connect("mistral").unwrap_err().starts_with("missing '@'"));
+        assert!(parse_connect("@192.168.1.5").unwrap_err().starts_with("no model"));
+        assert!(parse_connect("mistral@").unwrap_err().starts_with("no server"));
+    }
+}
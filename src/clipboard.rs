@@ -0,0 +1,34 @@
+use crate::{logging, status};
+
+// Function to copy the finished response to the system clipboard.
+// Headless machines (no display server) have no clipboard, so that only warns.
+pub fn copy_response(text: &str) {
+    let result = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text.to_string()));
+
+    match result {
+        Ok(_) => logging::info(&format!("{} Response copied to clipboard", status::ok())),
+        Err(e) => logging::info(&format!("{} Could not copy to clipboard: {}", status::warn(), e)),
+    }
+}
+
+// Function to read an image from the clipboard as PNG bytes.
+// The clipboard holds raw RGBA pixels, which are encoded so the server can decode them.
+pub fn read_image_png() -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let image = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_image())
+        .map_err(|e| match e {
+            arboard::Error::ContentNotAvailable => "The clipboard doesn't contain an image. Copy a screenshot or image first.".to_string(),
+            e => format!("Could not read the clipboard: {}", e),
+        })?;
+
+    let mut png_data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_data, image.width as u32, image.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&image.bytes)?;
+    }
+
+    Ok(png_data)
+}
@@ -0,0 +1,58 @@
+// Adds line numbers inside fenced code blocks of streamed text. Prose is passed
+// through as it arrives; lines inside a block are held until they are complete.
+#[derive(Default)]
+pub struct CodeNumberer {
+    line: String,
+    in_code: bool,
+    number: usize,
+}
+
+impl CodeNumberer {
+    // Function to feed a chunk of streamed text and get back what can be printed now
+    pub fn push(&mut self, chunk: &str) -> String {
+        let mut output = String::new();
+
+        for c in chunk.chars() {
+            self.line.push(c);
+            if !self.in_code {
+                output.push(c);
+            }
+
+            if c == '\n' {
+                self.end_line(&mut output);
+            }
+        }
+
+        output
+    }
+
+    // Function to emit a code line still buffered at the end of the stream
+    pub fn finish(&mut self) -> String {
+        let mut output = String::new();
+        if self.in_code && !self.line.is_empty() {
+            self.end_line(&mut output);
+        }
+        self.line.clear();
+        output
+    }
+
+    fn end_line(&mut self, output: &mut String) {
+        let is_fence = self.line.trim_start().starts_with("```");
+
+        if self.in_code {
+            if is_fence {
+                output.push_str(&self.line);
+            } else {
+                self.number += 1;
+                output.push_str(&format!("{:>3} | {}", self.number, self.line));
+            }
+        }
+
+        if is_fence {
+            self.in_code = !self.in_code;
+            self.number = 0;
+        }
+
+        self.line.clear();
+    }
+}
@@ -0,0 +1,85 @@
+use ollama_rs::generation::completion::request::GenerationRequest;
+
+use crate::metrics::Metrics;
+use crate::{client, error, logging, options, prompt, settings, status, streaming};
+
+// One side of the comparison: its label, server and model, and the metrics or the error
+struct Side {
+    label: &'static str,
+    server: String,
+    model: String,
+    result: Result<Metrics, String>,
+}
+
+// Function to run the prompt on one connection, streaming the answer under a clear heading
+async fn run_side(label: &'static str, local: bool, prompt: &str) -> Side {
+    let model = settings::model_for(local);
+    logging::info(&format!("\n=== {} ({}) ===", label, model));
+
+    let (ollama, server) = match client::build_client(local) {
+        Ok(client) => client,
+        Err(e) => {
+            println!("{} {} skipped: {}", status::err(), label, e);
+            return Side { label, server: "-".to_string(), model, result: Err(e.to_string()) };
+        }
+    };
+
+    let request_id = logging::start_request(prompt);
+    let request = options::apply(GenerationRequest::new(model.clone(), prompt.to_string()), &model);
+    let result = streaming::generate(&ollama, local, request, &model, &request_id, false).await
+        .map_err(|e| error::explain(e, &server).to_string());
+    if let Err(e) = &result {
+        println!("\n{} {} failed: {}", status::err(), label, e);
+    }
+
+    Side { label, server, model, result }
+}
+
+// Function to handle --compare-local-remote: send the same prompt to the remote server and
+// then the local instance (one after the other, so they don't compete), and print their
+// metrics side by side to show whether offloading to the server is actually faster.
+// Returns the faster side's answer, or the only one that succeeded.
+pub async fn compare_local_remote(prompt: String) -> Result<Option<Metrics>, Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+
+    let prompt = prompt::prepare(prompt);
+    let sides = [
+        run_side("Remote", false, &prompt).await,
+        run_side("Local", true, &prompt).await,
+    ];
+
+    let cell = |side: &Side, value: fn(&Metrics) -> String| match &side.result {
+        Ok(metrics) => value(metrics),
+        Err(_) => "failed".to_string(),
+    };
+    let rows: [(&str, fn(&Metrics) -> String); 4] = [
+        ("First token", |m| format!("{:.2}s", m.first_token_time)),
+        ("Tokens/sec", |m| format!("{:.1}", m.tps)),
+        ("Total time", |m| format!("{:.2}s", m.total_time)),
+        ("Tokens", |m| m.tokens.to_string()),
+    ];
+
+    println!("\n=== Remote vs Local ===");
+    println!("{:<14}{:<28}{:<28}", "", sides[0].label, sides[1].label);
+    println!("{:<14}{:<28}{:<28}", "Server", sides[0].server, sides[1].server);
+    println!("{:<14}{:<28}{:<28}", "Model", sides[0].model, sides[1].model);
+    for (name, value) in rows {
+        println!("{:<14}{:<28}{:<28}", name, cell(&sides[0], value), cell(&sides[1], value));
+    }
+
+    if let (Ok(remote), Ok(local)) = (&sides[0].result, &sides[1].result) {
+        let (faster, slower, ratio) = if remote.tps >= local.tps {
+            ("Remote", "local", remote.tps / local.tps.max(f64::EPSILON))
+        } else {
+            ("Local", "remote", local.tps / remote.tps.max(f64::EPSILON))
+        };
+        println!("{} {} generated {:.1}x faster than {}", status::ok(), faster, ratio, slower);
+    }
+
+    let [remote, local] = sides.map(|side| side.result.ok());
+    Ok(match (remote, local) {
+        (Some(remote), Some(local)) if local.tps > remote.tps => Some(local),
+        (Some(remote), _) => Some(remote),
+        (None, local) => local,
+    })
+}
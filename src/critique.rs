@@ -0,0 +1,33 @@
+use ollama_rs::Ollama;
+use ollama_rs::generation::completion::request::GenerationRequest;
+
+use crate::metrics::Metrics;
+use crate::{logging, options, settings, status};
+
+// Sent as a follow-up in the same conversation, so the model sees the answer it gave
+const CRITIQUE_PROMPT: &str = "Rate your previous answer from 1 to 10 for accuracy, completeness and clarity. \
+Then list its main weaknesses in at most three short bullet points.";
+
+// Function to ask the model to rate its own answer (--self-critique), as a quick quality signal
+// for prompt engineering. The follow-up carries the answer's conversation context, like
+// /continue does. It isn't streamed or exported, so --output and hooks keep the real answer.
+pub async fn self_critique(ollama: &Ollama, model: &str, metrics: &Metrics) {
+    if !settings::get().self_critique {
+        return;
+    }
+
+    let Some(context) = metrics.context.clone() else {
+        logging::info(&format!("{} Skipping self-critique: the server returned no conversation context", status::warn()));
+        return;
+    };
+
+    let request = options::apply(GenerationRequest::new(model.to_string(), CRITIQUE_PROMPT.to_string()), model)
+        .context(context);
+
+    logging::info("\n--- Self-Critique (the model rating its own answer) ---");
+    match ollama.generate(request).await {
+        Ok(response) => logging::info(response.response.trim()),
+        Err(e) => logging::info(&format!("{} Self-critique failed: {}", status::warn(), e)),
+    }
+    logging::info("------------------------------------------------------");
+}
@@ -0,0 +1,263 @@
+use std::env::VarError;
+use std::fmt;
+
+// Errors the client reports with a specific, user-facing message
+#[derive(Debug)]
+pub enum ClientError {
+    // A required .env variable is unset/empty or isn't valid UTF-8
+    MissingEnv { name: String, cause: VarError },
+    // Nothing is listening at the server address (connection refused)
+    Unreachable { url: String },
+    // Input ended (stdin closed or Ctrl-D) while waiting for the user
+    InputClosed,
+    // The requested model isn't installed on the server
+    ModelNotFound { model: String },
+    // The server has no models at all, e.g. a fresh install; `model` is the one to suggest pulling
+    NoModelsInstalled { model: String },
+    // A flag, file or setting given at startup is invalid
+    BadInput { message: String },
+    // The server answered with an error; `message` is the text of its {"error": "..."} body
+    Ollama { status: Option<u16>, message: String },
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::MissingEnv { name, cause: VarError::NotPresent } => {
+                write!(f, "{} must be set in .env file", name)
+            },
+            ClientError::MissingEnv { name, cause: VarError::NotUnicode(_) } => {
+                write!(f, "{} in .env file is not valid UTF-8", name)
+            },
+            ClientError::Unreachable { url } => {
+                write!(f, "Could not reach Ollama at {}. Is `ollama serve` running?\n", url)?;
+                write!(f, "Hint: start it with `ollama serve`, or install it from https://ollama.com/download")
+            },
+            ClientError::InputClosed => write!(f, "Input ended before anything was entered"),
+            ClientError::ModelNotFound { model } => {
+                write!(f, "Model '{}' is not installed on the server\n", model)?;
+                write!(f, "Hint: run with --pull {} to download it", model)
+            },
+            ClientError::NoModelsInstalled { model } => {
+                write!(f, "No models are installed. Pull one with --pull {} to get started.", model)
+            },
+            ClientError::BadInput { message } => write!(f, "{}", message),
+            ClientError::Ollama { status: Some(status), message } => write!(f, "{} (HTTP {})", message, status),
+            ClientError::Ollama { status: None, message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+// How a failed request should be treated by the retry logic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    // Worth retrying: timeouts, refused or reset connections, server-side (5xx) errors
+    Transient,
+    // Retrying won't help: missing model, bad request, missing configuration
+    Permanent,
+    Unknown,
+}
+
+// Function to classify an error for retries by looking through the chain of wrapped errors
+pub fn classify(error: &(dyn std::error::Error + 'static)) -> ErrorClass {
+    let mut current = Some(error);
+
+    while let Some(error) = current {
+        match error.downcast_ref::<ClientError>() {
+            // Server errors are judged by their status, or by their text when it's unknown
+            Some(ClientError::Ollama { status: Some(status), .. }) => {
+                return if *status >= 500 { ErrorClass::Transient } else { ErrorClass::Permanent };
+            },
+            Some(ClientError::Ollama { status: None, .. }) => break,
+            Some(_) => return ErrorClass::Permanent,
+            None => {},
+        }
+
+        if let Some(http_error) = error.downcast_ref::<reqwest::Error>() {
+            if http_error.is_timeout() || http_error.is_connect() {
+                return ErrorClass::Transient;
+            }
+            if let Some(status) = http_error.status() {
+                return if status.is_server_error() { ErrorClass::Transient } else { ErrorClass::Permanent };
+            }
+        }
+
+        if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
+            use std::io::ErrorKind::*;
+            if matches!(io_error.kind(), ConnectionRefused | ConnectionReset | ConnectionAborted | TimedOut | BrokenPipe | UnexpectedEof) {
+                return ErrorClass::Transient;
+            }
+        }
+
+        current = error.source();
+    }
+
+    // The server's own error messages only arrive as text
+    let message = error.to_string().to_lowercase();
+    if message.contains("not found") || message.contains("invalid") || message.contains("bad request") || message.contains("unauthorized") {
+        ErrorClass::Permanent
+    } else {
+        ErrorClass::Unknown
+    }
+}
+
+// Function to tell whether an error was caused by a refused connection (ECONNREFUSED),
+// looking through the chain of wrapped errors down to the underlying I/O error
+pub fn is_connection_refused(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut current = Some(error);
+
+    while let Some(error) = current {
+        if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
+            if io_error.kind() == std::io::ErrorKind::ConnectionRefused {
+                return true;
+            }
+        }
+        current = error.source();
+    }
+
+    false
+}
+
+// Function to pull the message out of an Ollama error body such as
+// {"error": "model 'xyz' not found, try pulling it first"}
+pub fn server_message(body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body.trim()).ok()?;
+    value.get("error")?.as_str().map(str::to_string)
+}
+
+// Function to turn an error status into ClientError::Ollama carrying the server's own message,
+// instead of a bare status code. Successful responses are passed through.
+pub async fn check_response(response: reqwest::Response) -> Result<reqwest::Response, ClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let message = server_message(&body)
+        .or_else(|| Some(body.trim().to_string()).filter(|body| !body.is_empty()))
+        .unwrap_or_else(|| status.canonical_reason().unwrap_or("request failed").to_string());
+    Err(ClientError::Ollama { status: Some(status.as_u16()), message })
+}
+
+// Function to replace a connection-refused error with an actionable message, and a raw
+// {"error": ...} body passed on by ollama-rs with just its message
+pub fn explain(error: Box<dyn std::error::Error>, url: &str) -> Box<dyn std::error::Error> {
+    if is_connection_refused(error.as_ref()) {
+        return Box::new(ClientError::Unreachable { url: url.to_string() });
+    }
+
+    match server_message(&error.to_string()) {
+        Some(message) => Box::new(ClientError::Ollama { status: None, message }),
+        None => error,
+    }
+}
+
+// Process exit codes, so scripts can branch on why a run failed
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_CONNECTION: i32 = 2;
+pub const EXIT_MODEL_NOT_FOUND: i32 = 3;
+pub const EXIT_BAD_INPUT: i32 = 4;
+pub const EXIT_TIMEOUT: i32 = 5;
+
+// Function to wrap a startup error (bad flag value, unreadable config file) so it exits with EXIT_BAD_INPUT
+pub fn bad_input(error: Box<dyn std::error::Error>) -> Box<dyn std::error::Error> {
+    if error.downcast_ref::<ClientError>().is_some() {
+        return error;
+    }
+    Box::new(ClientError::BadInput { message: error.to_string() })
+}
+
+// Function to pick the exit code for an error, looking through the chain of wrapped errors
+pub fn exit_code(error: &(dyn std::error::Error + 'static)) -> i32 {
+    let mut current = Some(error);
+
+    while let Some(error) = current {
+        if let Some(client_error) = error.downcast_ref::<ClientError>() {
+            return match client_error {
+                ClientError::Unreachable { .. } => EXIT_CONNECTION,
+                ClientError::ModelNotFound { .. } | ClientError::NoModelsInstalled { .. } => EXIT_MODEL_NOT_FOUND,
+                ClientError::MissingEnv { .. } | ClientError::InputClosed | ClientError::BadInput { .. } => EXIT_BAD_INPUT,
+                ClientError::Ollama { message, .. } if message.contains("model") && message.contains("not found") => EXIT_MODEL_NOT_FOUND,
+                ClientError::Ollama { .. } => EXIT_FAILURE,
+            };
+        }
+
+        if let Some(http_error) = error.downcast_ref::<reqwest::Error>() {
+            if http_error.is_timeout() {
+                return EXIT_TIMEOUT;
+            }
+            if http_error.is_connect() {
+                return EXIT_CONNECTION;
+            }
+        }
+
+        if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
+            use std::io::ErrorKind::*;
+            match io_error.kind() {
+                TimedOut => return EXIT_TIMEOUT,
+                ConnectionRefused | ConnectionReset | ConnectionAborted => return EXIT_CONNECTION,
+                _ => {},
+            }
+        }
+
+        current = error.source();
+    }
+
+    // The server's missing-model error only arrives as text
+    let message = error.to_string();
+    if message.contains("model") && message.contains("not found") {
+        EXIT_MODEL_NOT_FOUND
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A response as reqwest would hand it over, without a server
+    fn response(status: u16, body: &str) -> reqwest::Response {
+        reqwest::Response::from(http::Response::builder().status(status).body(body.to_string()).unwrap())
+    }
+
+    #[test]
+    fn server_message_reads_the_error_field() {
+        assert_eq!(server_message(r#"{"error":"model 'xyz' not found, try pulling it first"}"#), Some("model 'xyz' not found, try pulling it first".to_string()));
+        assert_eq!(server_message(" {\"error\": \"boom\"}\n"), Some("boom".to_string()));
+    }
+
+    #[test]
+    fn server_message_ignores_other_bodies() {
+        assert_eq!(server_message("Internal Server Error"), None);
+        assert_eq!(server_message(r#"{"status":"success"}"#), None);
+        assert_eq!(server_message(r#"{"error":{"code":1}}"#), None);
+    }
+
+    #[tokio::test]
+    async fn check_response_surfaces_the_server_message() {
+        let error = check_response(response(404, r#"{"error":"model 'xyz' not found, try pulling it first"}"#)).await.unwrap_err();
+        assert_eq!(error.to_string(), "model 'xyz' not found, try pulling it first (HTTP 404)");
+        assert_eq!(classify(&error), ErrorClass::Permanent);
+        assert_eq!(exit_code(&error), EXIT_MODEL_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn check_response_falls_back_to_the_body_or_status() {
+        let error = check_response(response(502, "bad gateway\n")).await.unwrap_err();
+        assert_eq!(error.to_string(), "bad gateway (HTTP 502)");
+        assert_eq!(classify(&error), ErrorClass::Transient);
+
+        let error = check_response(response(500, "")).await.unwrap_err();
+        assert_eq!(error.to_string(), "Internal Server Error (HTTP 500)");
+    }
+
+    #[tokio::test]
+    async fn check_response_passes_success_through() {
+        let response = check_response(response(200, r#"{"version":"0.5.0"}"#)).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), r#"{"version":"0.5.0"}"#);
+    }
+}
@@ -0,0 +1,118 @@
+use pulldown_cmark::{html, Options, Parser};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+use crate::metrics::Metrics;
+use crate::{clipboard, hooks, logging, settings, status};
+
+// How the finished response is written to --output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ResponseFormat {
+    // The response exactly as the model wrote it
+    #[default]
+    Text,
+    // The response's markdown rendered as an HTML fragment, for embedding in web pages
+    MarkdownToHtml,
+}
+
+// Function to check an output path before anything is sent, so a bad path doesn't waste a generation
+pub fn validate_output(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let path = Path::new(path);
+
+    if path.is_dir() {
+        return Err(format!("{} is a directory, expected a file path", path.display()).into());
+    }
+
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() && !parent.is_dir() => {
+            Err(format!("Output directory {} does not exist", parent.display()).into())
+        },
+        _ => Ok(()),
+    }
+}
+
+// Function to convert markdown to an HTML fragment (tables, strikethrough and task lists included)
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut output = String::new();
+    html::push_html(&mut output, Parser::new_ext(markdown, options));
+    output
+}
+
+// Function to write the finished response to --output in the chosen format
+pub fn write_response(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let settings = settings::get();
+    let Some(path) = &settings.output else {
+        return Ok(());
+    };
+
+    let (contents, kind) = match settings.format_response {
+        ResponseFormat::Text => (text.to_string(), "text"),
+        ResponseFormat::MarkdownToHtml => (markdown_to_html(text), "HTML"),
+    };
+
+    fs::write(path, &contents).map_err(|e| format!("Could not write {}: {}", path, e))?;
+    logging::info(&format!("{} Wrote {} bytes of {} to {}", status::ok(), contents.len(), kind, path));
+    Ok(())
+}
+
+// Function to write the final response object for --save-response-json. The server only fills
+// the context, durations and counts on the final chunk, so those come from it, while `response`
+// holds the full text streamed before it.
+pub fn write_response_json(path: &str, metrics: &Metrics) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(resp) = &metrics.final_response else {
+        logging::info(&format!("{} No final response received; {} not written", status::warn(), path));
+        return Ok(());
+    };
+
+    let object = json!({
+        "model": resp.model,
+        "created_at": resp.created_at,
+        "response": metrics.response,
+        "done": resp.done,
+        "done_reason": metrics.done_reason,
+        "context": resp.context,
+        "total_duration": resp.total_duration,
+        "load_duration": resp.load_duration,
+        "prompt_eval_count": resp.prompt_eval_count,
+        "prompt_eval_duration": resp.prompt_eval_duration,
+        "eval_count": resp.eval_count,
+        "eval_duration": resp.eval_duration,
+    });
+
+    fs::write(path, serde_json::to_string_pretty(&object)?)
+        .map_err(|e| format!("Could not write {}: {}", path, e))?;
+    logging::info(&format!("{} Saved the final response object to {}", status::ok(), path));
+    Ok(())
+}
+
+// Function to tell whether anything wants the final answer (--clipboard, --output,
+// --save-response-json or a post-hook), e.g. before asking which candidate to keep
+pub fn wants_response() -> bool {
+    let settings = settings::get();
+    settings.clipboard
+        || settings.output.is_some()
+        || settings.save_response_json.is_some()
+        || settings.post_hook.is_some()
+        || settings.post_hook_file.is_some()
+}
+
+// Function to hand a command's final answer to --clipboard, --output, --save-response-json
+// and the post-hook. It runs once per command, so commands that stream several answers
+// (candidates, comparisons, a REPL session) don't overwrite files or run the hook per answer.
+pub fn deliver(metrics: &Metrics) -> Result<(), Box<dyn std::error::Error>> {
+    if settings::get().clipboard {
+        clipboard::copy_response(&metrics.response);
+    }
+    write_response(&metrics.response)?;
+    if let Some(path) = &settings::get().save_response_json {
+        write_response_json(path, metrics)?;
+    }
+    hooks::run_post_hook(&metrics.response);
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::{logging, settings, status};
+
+// Function to build the command for --post-hook (run by the shell) or --post-hook-file (run directly)
+fn hook_command() -> Option<(Command, String)> {
+    let settings = settings::get();
+
+    if let Some(script) = &settings.post_hook_file {
+        return Some((Command::new(script), script.clone()));
+    }
+
+    let hook = settings.post_hook.as_ref()?;
+    let command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", hook]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", hook]);
+        command
+    };
+    Some((command, hook.clone()))
+}
+
+// Function to pipe the finished response into the post-processing hook and print what it outputs.
+// The hook's stderr goes straight to the terminal; a failing hook is reported but isn't fatal.
+pub fn run_post_hook(text: &str) {
+    let Some((mut command, name)) = hook_command() else {
+        return;
+    };
+
+    let child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            logging::info(&format!("{} Could not run post-hook '{}': {}", status::err(), name, e));
+            return;
+        }
+    };
+
+    // Feed stdin from another thread so a hook that writes a lot before reading can't deadlock
+    let input = text.to_string();
+    let stdin = child.stdin.take();
+    let writer = std::thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(input.as_bytes());
+        }
+    });
+
+    let output = child.wait_with_output();
+    let _ = writer.join();
+
+    match output {
+        Ok(output) => {
+            logging::info("\n--- Post-hook Output ---");
+            print!("{}", String::from_utf8_lossy(&output.stdout));
+            let _ = std::io::stdout().flush();
+
+            if !output.status.success() {
+                let code = output.status.code().map(|code| code.to_string()).unwrap_or_else(|| "a signal".to_string());
+                logging::info(&format!("{} Post-hook '{}' failed (exit status {})", status::err(), name, code));
+            }
+        },
+        Err(e) => logging::info(&format!("{} Post-hook '{}' failed: {}", status::err(), name, e)),
+    }
+}
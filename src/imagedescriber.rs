@@ -1,20 +1,41 @@
 use ollama_rs::{Ollama, generation::completion::request::GenerationRequest, generation::images::Image};
-use tokio::io::{self, AsyncWriteExt};
-use tokio_stream::StreamExt;
-use std::io::{stdin, stdout, Write};
+use std::io::{stdin, stdout, IsTerminal, Read};
 use std::fs;
-use std::path::Path;
-use std::time::Instant;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use base64::{Engine as _, engine::general_purpose};
+use dialoguer::Select;
+use tokio::sync::Semaphore;
 
-// Function to get user input with a prompt
-pub fn get_user_input(prompt: &str) -> String {
-    print!("{}", prompt);
-    stdout().flush().unwrap();
-    
-    let mut input = String::new();
-    stdin().read_line(&mut input).expect("Failed to read input");
-    input.trim().to_string()
+use crate::metrics::Metrics;
+use crate::{client, clipboard, error, lineeditor, logging, modelmanager, options, prompt, settings, status, streaming, warmup};
+
+// Image file extensions accepted in ./images/ (lowercase)
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "bmp", "webp"];
+
+// How each supported format is recognized from its first bytes: every (offset, bytes) part
+// must match. Each extension above needs a signature here, so keep the two lists in step.
+struct ImageSignature {
+    format: &'static str,
+    extensions: &'static [&'static str],
+    magic: &'static [(usize, &'static [u8])],
+}
+
+const IMAGE_SIGNATURES: &[ImageSignature] = &[
+    ImageSignature { format: "png", extensions: &["png"], magic: &[(0, b"\x89PNG\r\n\x1a\n")] },
+    ImageSignature { format: "jpg", extensions: &["jpg", "jpeg"], magic: &[(0, &[0xFF, 0xD8, 0xFF])] },
+    ImageSignature { format: "gif", extensions: &["gif"], magic: &[(0, b"GIF87a")] },
+    ImageSignature { format: "gif", extensions: &["gif"], magic: &[(0, b"GIF89a")] },
+    ImageSignature { format: "bmp", extensions: &["bmp"], magic: &[(0, b"BM")] },
+    ImageSignature { format: "webp", extensions: &["webp"], magic: &[(0, b"RIFF"), (8, b"WEBP")] },
+];
+
+// Function to get user input with a prompt; None when input has ended
+pub fn get_user_input(prompt: &str) -> Option<String> {
+    lineeditor::read_line(prompt)
 }
 
 // Function to list available images in the images directory
@@ -29,68 +50,370 @@ fn list_images() -> Result<Vec<String>, Box<dyn std::error::Error>> {
     }
     
     let mut image_files = Vec::new();
+    let mut visited = HashSet::new();
+    visited.insert(fs::canonicalize(images_dir)?);
+    
+    collect_images(images_dir, Path::new(""), &mut visited, &mut image_files)?;
     
-    for entry in fs::read_dir(images_dir)? {
+    image_files.sort();
+    Ok(image_files)
+}
+
+// Function to collect images from a directory. Symlinked image files are included when their
+// target really is an image; linked directories are only searched with --follow-symlinks.
+fn collect_images(dir: &Path, prefix: &Path, visited: &mut HashSet<PathBuf>, image_files: &mut Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let follow_symlinks = settings::get().follow_symlinks;
+    
+    for entry in fs::read_dir(dir)? {
         let entry = entry?;
         let path = entry.path();
+        let relative = prefix.join(entry.file_name());
         
-        if let Some(extension) = path.extension() {
-            let ext = extension.to_string_lossy().to_lowercase();
-            if matches!(ext.as_str(), "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp") {
-                if let Some(filename) = path.file_name() {
-                    image_files.push(filename.to_string_lossy().to_string());
+        if entry.file_type()?.is_symlink() {
+            // Resolve the link target; broken links are skipped
+            let Ok(target) = fs::canonicalize(&path) else {
+                continue;
+            };
+            
+            if target.is_dir() {
+                // Remember every directory we enter so symlink loops can't recurse forever
+                if follow_symlinks && visited.insert(target.clone()) {
+                    collect_images(&target, &relative, visited, image_files)?;
                 }
+                continue;
             }
+            
+            // A link named like an image may point anywhere, so check what it points to
+            if !has_image_signature(&target) {
+                continue;
+            }
+        }
+        
+        if path.is_file() && is_supported_image(&path) {
+            image_files.push(image_name(&relative));
         }
     }
     
-    image_files.sort();
-    Ok(image_files)
+    Ok(())
+}
+
+// Function to name an image by its path relative to ./images/, always with / separators,
+// so names look the same on Windows and can be typed back on any platform
+fn image_name(relative: &Path) -> String {
+    relative.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Function to find an image given by name under ./images/. Names may use / on any platform,
+// a path copied from the project root such as ./images/cat.png also works, and absolute
+// paths are used as they are.
+fn image_path(name: &str) -> PathBuf {
+    image_path_in(Path::new("./images"), name)
+}
+
+// Function to resolve an image name under `images_dir`. \ is a separator on Windows, but on
+// other platforms it can be part of a file name, so there a name like cats\tabby.jpg (typed
+// as on Windows) is only split on \ when no file matches it as written.
+fn image_path_in(images_dir: &Path, name: &str) -> PathBuf {
+    if Path::new(name).is_absolute() {
+        return PathBuf::from(name);
+    }
+    
+    if cfg!(windows) {
+        return join_image_name(images_dir, name, &['/', '\\']);
+    }
+    
+    let literal = join_image_name(images_dir, name, &['/']);
+    if literal.exists() || !name.contains('\\') {
+        return literal;
+    }
+    join_image_name(images_dir, name, &['/', '\\'])
+}
+
+// Function to join the parts of an image name onto the images directory, dropping a leading
+// "images" part when the name was given relative to the project root
+fn join_image_name(images_dir: &Path, name: &str, separators: &[char]) -> PathBuf {
+    let parts: Vec<&str> = name.split(separators)
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+    let join = |parts: &[&str]| parts.iter().fold(images_dir.to_path_buf(), |path, part| path.join(part));
+    
+    let path = join(&parts);
+    match parts.split_first() {
+        Some((&"images", rest)) if !path.exists() && !rest.is_empty() => join(rest),
+        _ => path,
+    }
+}
+
+// Function to check whether a file has one of the supported image extensions
+fn is_supported_image(path: &Path) -> bool {
+    match path.extension() {
+        Some(extension) => {
+            let ext = extension.to_string_lossy().to_lowercase();
+            SUPPORTED_EXTENSIONS.contains(&ext.as_str())
+        },
+        None => false,
+    }
+}
+
+// Function to print the accepted image extensions for --list-formats
+pub fn list_formats() {
+    println!("Supported image formats:");
+    for ext in SUPPORTED_EXTENSIONS {
+        println!("  - {}", ext);
+    }
 }
 
 // Function to create Image object from file path
 fn create_image_from_file(image_path: &Path) -> Result<Image, Box<dyn std::error::Error>> {
     let image_data = fs::read(image_path)?;
-    let base64_string = general_purpose::STANDARD.encode(&image_data);
+    create_image_from_bytes(&image_data)
+}
+
+// Function to create Image object from raw image bytes
+fn create_image_from_bytes(image_data: &[u8]) -> Result<Image, Box<dyn std::error::Error>> {
+    let base64_string = general_purpose::STANDARD.encode(image_data);
+    
+    // Base64 grows the data by a third; catch an oversized request here instead of a bare 413
+    let limit = settings::get().max_image_bytes;
+    if base64_string.len() as u64 > limit {
+        let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        return Err(format!(
+            "Encoded image is {:.1} MB, exceeds the {:.1} MB limit (--max-image-bytes). Use a smaller or more compressed image, or raise the limit if your server accepts larger requests",
+            mb(base64_string.len() as u64), mb(limit)
+        ).into());
+    }
+    
+    // Debugging aid: save exactly what will be sent to the server
+    if let Some(dump_path) = &settings::get().dump_image_payload {
+        fs::write(dump_path, &base64_string)?;
+        println!("Wrote base64 image payload ({} bytes) to {}", base64_string.len(), dump_path);
+    }
     
     // Create Image object with base64 data
     let image = Image::from_base64(&base64_string);
     Ok(image)
 }
 
-// Function to determine connection type (server first, then local fallback)
-fn should_use_local() -> bool {
-    dotenv::dotenv().ok();
+// Largest image --image - reads from stdin, so an endless stream can't exhaust memory
+const STDIN_IMAGE_LIMIT: u64 = 20 * 1024 * 1024;
+
+// Function to name an image's format from its magic bytes, or None if it isn't a supported image
+fn detect_image_format(data: &[u8]) -> Option<&'static str> {
+    IMAGE_SIGNATURES.iter()
+        .find(|signature| signature.magic.iter()
+            .all(|(offset, bytes)| data.get(*offset..offset + bytes.len()) == Some(*bytes)))
+        .map(|signature| signature.format)
+}
+
+// Function to check a file's first bytes against the supported image signatures
+fn has_image_signature(path: &Path) -> bool {
+    let mut header = Vec::new();
+    fs::File::open(path)
+        .and_then(|file| file.take(16).read_to_end(&mut header))
+        .is_ok_and(|_| detect_image_format(&header).is_some())
+}
+
+// Function to read raw image bytes piped in for --image -, e.g. from a screenshot tool
+fn read_stdin_image() -> Result<Image, Box<dyn std::error::Error>> {
+    if stdin().is_terminal() {
+        return Err("--image - expects image data piped on stdin, e.g. cat photo.png | ... --image -".into());
+    }
     
-    // Check if server_ip is set
-    if let Ok(server_ip) = std::env::var("server_ip") {
-        // If server_ip is explicitly set to localhost, use local
-        if server_ip.contains("localhost") || server_ip.contains("127.0.0.1") {
-            return true;
+    let mut image_data = Vec::new();
+    stdin().lock().take(STDIN_IMAGE_LIMIT + 1).read_to_end(&mut image_data)?;
+    if image_data.len() as u64 > STDIN_IMAGE_LIMIT {
+        return Err(format!("Image on stdin is larger than {} MB", STDIN_IMAGE_LIMIT / (1024 * 1024)).into());
+    }
+    if image_data.is_empty() {
+        return Err("No image data on stdin".into());
+    }
+    
+    let format = detect_image_format(&image_data)
+        .ok_or_else(|| format!("Data on stdin is not a supported image ({})", SUPPORTED_EXTENSIONS.join(", ")))?;
+    logging::info(&format!("Read {} image from stdin ({} bytes)", format, image_data.len()));
+    create_image_from_bytes(&image_data)
+}
+
+// Function to tell whether --image names a URL rather than a file in ./images/
+fn is_image_url(name: &str) -> bool {
+    name.starts_with("http://") || name.starts_with("https://")
+}
+
+// How long an image download may take before it is abandoned
+const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Function to download an image given as a URL, optionally keeping a copy (--save-fetched).
+// Uses the shared HTTP client (--header values included) and stops reading as soon as the
+// image can't fit under --max-image-bytes, so a huge download never ends up in memory.
+async fn fetch_image(url: &str) -> Result<Image, Box<dyn std::error::Error>> {
+    logging::info(&format!("Downloading image: {}", url));
+    let http_client = client::http_client_for_url(&reqwest::Url::parse(url)?)?;
+    let mut response = http_client.get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let content_type = response.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    if let Some(content_type) = &content_type {
+        if !content_type.starts_with("image/") {
+            return Err(format!("{} is not an image (content type {})", url, content_type).into());
         }
-        // If server_ip is set to a remote address, try server first
-        return false;
-    } else {
-        // No server_ip set - still default to server (false) to try remote first
-        // This will cause an error which can be caught and fallback to local
-        return false;
     }
+
+    // The limit applies to the base64 payload, which is 4/3 of the raw size
+    let max_image_bytes = settings::get().max_image_bytes;
+    let limit = max_image_bytes / 4 * 3;
+    let too_large = || format!(
+        "{} is larger than the {:.1} MB image limit (--max-image-bytes)",
+        url, max_image_bytes as f64 / (1024.0 * 1024.0)
+    );
+    if response.content_length().is_some_and(|length| length > limit) {
+        return Err(too_large().into());
+    }
+
+    let mut image_data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if (image_data.len() + chunk.len()) as u64 > limit {
+            return Err(too_large().into());
+        }
+        image_data.extend_from_slice(&chunk);
+    }
+
+    if settings::get().save_fetched {
+        let path = save_fetched_image(url, content_type.as_deref(), &image_data)?;
+        println!("{} Saved image to {}", status::ok(), path.display());
+    }
+
+    create_image_from_bytes(&image_data)
 }
 
+// Function to store a downloaded image in ./images/ under a name derived from the URL.
+// Existing files are never overwritten: photo.jpg becomes photo-1.jpg, photo-2.jpg, ...
+fn save_fetched_image(url: &str, content_type: Option<&str>, image_data: &[u8]) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let images_dir = Path::new("./images");
+    fs::create_dir_all(images_dir)?;
 
-// Main function to analyze images interactively
-pub async fn analyze_image() -> Result<(), Box<dyn std::error::Error>> {
-    println!("\n=== Image Analysis ===");
+    // Last path segment without query or fragment, limited to safe filename characters
+    let segment = url.split(['?', '#']).next().unwrap_or(url)
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    let sanitized: String = segment.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect();
+    let sanitized = sanitized.trim_matches('.');
+
+    let candidate = Path::new(if sanitized.is_empty() { "image" } else { sanitized });
+    let stem = candidate.file_stem().and_then(|stem| stem.to_str()).unwrap_or("image").to_string();
+    let extension = match candidate.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if SUPPORTED_EXTENSIONS.contains(&ext.to_lowercase().as_str()) => ext.to_string(),
+        // No usable extension in the URL: take it from the content type
+        _ => match content_type.and_then(|value| value.strip_prefix("image/")) {
+            Some("jpeg") | None => "jpg".to_string(),
+            Some(subtype) => subtype.chars().take_while(|c| c.is_ascii_alphanumeric()).collect(),
+        },
+    };
+
+    let mut path = images_dir.join(format!("{}.{}", stem, extension));
+    let mut counter = 1;
+    while path.exists() {
+        path = images_dir.join(format!("{}-{}.{}", stem, counter, extension));
+        counter += 1;
+    }
+
+    fs::write(&path, image_data)?;
+    Ok(path)
+}
+
+// Function to get the default image prompt: --image-prompt, then image_prompt in .env, then built-in
+fn default_image_prompt() -> String {
+    settings::load_dotenv();
     
-    // List available images
-    let image_files = list_images()?;
+    if let Some(prompt) = &settings::get().image_prompt {
+        return prompt.clone();
+    }
     
-    if image_files.is_empty() {
-        println!("No images found in ./images/ directory.");
-        println!("Supported formats: jpg, jpeg, png, gif, bmp, webp");
-        return Ok(());
+    settings::env_var("image_prompt")
+        .unwrap_or_else(|_| "Describe this image in detail.".to_string())
+}
+
+// Function to get the vision model from .env (llava is common for image analysis)
+fn vision_model() -> String {
+    settings::env_var("vision_model")
+        .unwrap_or_else(|_| "llava".to_string())
+}
+
+// Name fragments of common vision-capable model families
+const VISION_MODEL_HINTS: &[&str] = &["llava", "vision", "moondream", "minicpm-v", "qwen2.5vl", "qwen2-vl", "gemma3", "bakllava"];
+
+// Function to make sure the vision model is installed, offering an installed alternative if not.
+// If the model list can't be fetched the request goes ahead and reports its own error.
+async fn resolve_vision_model(ollama: &Ollama, url: &str, model: String) -> Result<String, Box<dyn std::error::Error>> {
+    let installed = match modelmanager::list_models_cached(ollama, url).await {
+        Ok(models) => models,
+        Err(_) => return Ok(model),
+    };
+    
+    match modelmanager::check_installed(&installed, &model) {
+        Ok(()) => return Ok(model),
+        Err(e @ error::ClientError::NoModelsInstalled { .. }) => return Err(e.into()),
+        Err(_) => {},
     }
     
+    // Match hints against the model itself, not the registry or user in e.g. hf.co/vision-lab/model
+    let alternatives: Vec<String> = installed.iter()
+        .map(|m| m.name.clone())
+        .filter(|name| {
+            let (base, _) = modelmanager::split_tag(name);
+            let model_name = base.rsplit('/').next().unwrap_or(base).to_lowercase();
+            VISION_MODEL_HINTS.iter().any(|hint| model_name.contains(hint))
+        })
+        .collect();
+    
+    println!("{} Vision model '{}' is not installed on {}", status::warn(), model, url);
+    
+    // Only ask when someone is at the keyboard; scripts get the hint
+    if alternatives.is_empty() || !(stdin().is_terminal() && stdout().is_terminal()) {
+        let mut message = format!("Vision model '{}' is not installed. Run with --pull {} to download it", model, model);
+        if !alternatives.is_empty() {
+            message.push_str(&format!(", or set vision_model to one of: {}", alternatives.join(", ")));
+        }
+        return Err(message.into());
+    }
+    
+    let index = Select::new()
+        .with_prompt("Use an installed vision model instead?")
+        .items(&alternatives)
+        .item("No, cancel")
+        .default(0)
+        .interact()?;
+    
+    match alternatives.get(index) {
+        Some(alternative) => Ok(alternative.clone()),
+        None => Err(format!("Cancelled. Run with --pull {} to download it", model).into()),
+    }
+}
+
+// Function to pick an image with arrow keys and Enter
+fn select_image_interactive(image_files: &[String]) -> Result<usize, Box<dyn std::error::Error>> {
+    let index = Select::new()
+        .with_prompt("Select an image (arrow keys, Enter to confirm)")
+        .items(image_files)
+        .default(0)
+        .interact()?;
+    Ok(index)
+}
+
+// Function to pick an image by typing its number
+fn select_image_numeric(image_files: &[String]) -> Result<usize, Box<dyn std::error::Error>> {
     // Display available images
     println!("Available images:");
     for (i, filename) in image_files.iter().enumerate() {
@@ -98,211 +421,465 @@ pub async fn analyze_image() -> Result<(), Box<dyn std::error::Error>> {
     }
     
     // Get user selection
-    let selection = get_user_input("\nSelect an image (enter number): ");
-    let index: usize = selection.parse::<usize>()
-        .map_err(|_| "Invalid selection")?
-        .saturating_sub(1);
+    let selection = get_user_input("\nSelect an image (enter number): ").ok_or(error::ClientError::InputClosed)?;
+    parse_selection(&selection, image_files.len()).map_err(|e| e.into())
+}
+
+// Function to turn a typed 1-based selection into an index; "0", negative numbers,
+// non-numbers and numbers past the end are all rejected
+pub fn parse_selection(selection: &str, count: usize) -> Result<usize, String> {
+    let out_of_range = || format!("Please enter a number between 1 and {}.", count);
+
+    let selection = selection.trim();
+    let digits = selection.strip_prefix('-').unwrap_or(selection);
+
+    match selection.parse::<usize>() {
+        Ok(number) if (1..=count).contains(&number) => Ok(number - 1),
+        // Zero, negative and huge numbers are still numbers, just not one on the list
+        _ if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) => Err(out_of_range()),
+        _ => Err(format!("'{}' is not a number. {}", selection, out_of_range())),
+    }
+}
+
+// Main function to analyze images interactively
+pub async fn analyze_image() -> Result<Option<Metrics>, Box<dyn std::error::Error>> {
+    println!("\n=== Image Analysis ===");
     
-    if index >= image_files.len() {
-        return Err("Invalid image selection".into());
+    // List available images
+    let image_files = list_images()?;
+    
+    if image_files.is_empty() {
+        println!("No images found in ./images/ directory.");
+        println!("Supported formats: {}", SUPPORTED_EXTENSIONS.join(", "));
+        return Ok(None);
     }
     
+    // Arrow-key picker for terminals, numbered list for scripts and pipes
+    let index = if stdin().is_terminal() && stdout().is_terminal() {
+        select_image_interactive(&image_files)?
+    } else {
+        select_image_numeric(&image_files)?
+    };
+    
     let selected_image = &image_files[index];
     
     // Get custom prompt or use default
-    let custom_prompt = get_user_input("Enter custom prompt (or press Enter for default description): ");
+    let custom_prompt = get_user_input("Enter custom prompt (or press Enter for default description): ")
+        .ok_or(error::ClientError::InputClosed)?;
     let prompt = if custom_prompt.is_empty() {
-        "Describe this image in detail.".to_string()
+        default_image_prompt()
     } else {
         custom_prompt
     };
     
-    analyze_image_with_prompt(selected_image, &prompt).await
+    analyze_image_with_prompt(selected_image, &prompt).await.map(Some)
 }
 
-// Function to analyze a specific image (for command line use)
-pub async fn analyze_specific_image(filename: String) -> Result<(), Box<dyn std::error::Error>> {
-    let prompt = "Describe this image in detail.";
-    analyze_image_with_prompt(&filename, prompt).await
+// Function to analyze a specific image (for command line use); --prompt replaces the default image prompt
+pub async fn analyze_specific_image(filename: String, prompt: Option<String>) -> Result<Metrics, Box<dyn std::error::Error>> {
+    let prompt = prompt.unwrap_or_else(default_image_prompt);
+    analyze_image_with_prompt(&filename, &prompt).await
 }
 
 // Core function to analyze an image with a given prompt
-async fn analyze_image_with_prompt(filename: &str, prompt: &str) -> Result<(), Box<dyn std::error::Error>> {
-    dotenv::dotenv().ok();
-    
-    // Load image and create Image object
-    let image_path = Path::new("./images").join(filename);
-    
-    if !image_path.exists() {
-        return Err(format!("Image file not found: {}", filename).into());
-    }
+async fn analyze_image_with_prompt(filename: &str, prompt: &str) -> Result<Metrics, Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+    
+    // Load image (or download it when given a URL, or read it from stdin for "-") and create Image object
+    let image = if filename == "-" {
+        read_stdin_image()?
+    } else if is_image_url(filename) {
+        fetch_image(filename).await?
+    } else {
+        let image_path = image_path(filename);
+        
+        if !image_path.exists() {
+            return Err(format!("Image file not found: {}", filename).into());
+        }
+        
+        logging::info(&format!("Loading image: {}", filename));
+        create_image_from_file(&image_path)?
+    };
     
-    println!("Loading image: {}", filename);
-    let image = create_image_from_file(&image_path)?;
+    let label = if filename == "-" { "stdin" } else { filename };
+    analyze_loaded_image(image, label, prompt).await
+}
+
+// Function to analyze the image currently on the clipboard, e.g. a fresh screenshot (--image-clipboard);
+// --prompt replaces the default image prompt, like it does for --image
+pub async fn analyze_clipboard_image(prompt: Option<String>) -> Result<Metrics, Box<dyn std::error::Error>> {
+    settings::load_dotenv();
     
-    // Try server first, then fallback to local
-    let use_local = should_use_local();
+    logging::info("Reading image from clipboard");
+    let png_data = clipboard::read_image_png()?;
+    let image = create_image_from_bytes(&png_data)?;
     
-    let (ollama, connection_info) = if !use_local {
-        // Try remote server first
-        match std::env::var("server_ip") {
-            Ok(server_ip) => {
-                let server_url = format!("http://{}", server_ip);
-                println!("Attempting to use remote server: {}:11434", server_url);
-                (Ollama::new(server_url.clone(), 11434), format!("{}:11434", server_url))
+    let prompt = prompt.unwrap_or_else(default_image_prompt);
+    analyze_loaded_image(image, "clipboard image", &prompt).await
+}
+
+// Function to send a loaded image to the vision model and stream the description.
+// `label` names the image in the metrics, e.g. its filename.
+async fn analyze_loaded_image(image: Image, label: &str, prompt: &str) -> Result<Metrics, Box<dyn std::error::Error>> {
+    let prompt = prompt::prepare(prompt.to_string());
+    let request_id = logging::start_request(&prompt);
+    
+    // Try each connection the policy allows, falling back to the next one on failure
+    let order = client::connection_order();
+    let mut failures = Vec::new();
+    let mut connected = None;
+    
+    for (attempt, &local) in order.iter().enumerate() {
+        let kind = if local { "Local" } else { "Remote" };
+        if attempt > 0 {
+            println!("{} Falling back to {} Ollama instance...", status::retry(), kind.to_lowercase());
+        }
+        
+        let (ollama, connection_info) = match client::build_client(local) {
+            Ok(client) => client,
+            Err(e) => {
+                failures.push(FailedAttempt { local, failure: Failure::Other, message: e.to_string() });
+                continue;
+            }
+        };
+        logging::info(&format!("Using {} Ollama: {}", kind.to_lowercase(), connection_info));
+        
+        let model = match resolve_vision_model(&ollama, &connection_info, vision_model()).await {
+            Ok(model) => model,
+            Err(e) => {
+                failures.push(FailedAttempt { local, failure: Failure::MissingModel(vision_model()), message: e.to_string() });
+                continue;
+            }
+        };
+        logging::info(&format!("Using model: {}", model));
+        logging::info("Analyzing image...");
+        
+        // Create the request with image
+        let request = options::apply(GenerationRequest::new(model.clone(), prompt.to_string()), &model)
+            .images(vec![image.clone()]);
+        
+        logging::info("");
+        logging::info("--- Image Analysis ---");
+        
+        // The shared path retries (--retries, --retry-on-empty) before giving up on this connection.
+        // The vision model was checked above, so it never stops to offer a pull.
+        match streaming::generate(&ollama, local, request, &model, &request_id, false).await {
+            Ok(metrics) => {
+                connected = Some((metrics, kind));
+                break;
             },
-            Err(_) => {
-                println!("No server_ip configured, falling back to local");
-                (Ollama::new("http://localhost", 11434), "http://localhost:11434".to_string())
+            Err(e) => {
+                let failure = match e.downcast_ref::<error::ClientError>() {
+                    Some(error::ClientError::ModelNotFound { .. } | error::ClientError::NoModelsInstalled { .. }) => Failure::MissingModel(model),
+                    _ if modelmanager::is_missing_model(e.as_ref()) => Failure::MissingModel(model),
+                    _ if error::is_connection_refused(e.as_ref()) => Failure::Unreachable,
+                    _ => Failure::Other,
+                };
+                let e = error::explain(e, &connection_info);
+                println!("{} {} server failed: {}", status::err(), kind, e);
+                failures.push(FailedAttempt { local, failure, message: e.to_string() });
             }
         }
-    } else {
-        println!("Using local Ollama instance");
-        (Ollama::new("http://localhost", 11434), "http://localhost:11434".to_string())
+    }
+    
+    let Some((mut metrics, kind)) = connected else {
+        return Err(explain_failures(&failures).into());
     };
     
-    // Use a vision model (llava is common for image analysis)
-    let model = std::env::var("vision_model")
-        .unwrap_or_else(|_| "llava".to_string());
+    metrics.image = Some(label.to_string());
+    metrics.connection = Some(kind.to_string());
+    metrics.print(settings::get().metrics_format.as_deref());
     
-    println!("Using model: {}", model);
-    println!("Analyzing image...");
+    Ok(metrics)
+}
+
+// Why a connection was given up on while falling back
+enum Failure {
+    // Nothing was listening at the server address
+    Unreachable,
+    // The server answered but doesn't have this vision model
+    MissingModel(String),
+    Other,
+}
+
+struct FailedAttempt {
+    local: bool,
+    failure: Failure,
+    message: String,
+}
+
+// Function to sum up why every connection failed, e.g. "remote unreachable + local missing model 'llava'",
+// with a --pull hint for each instance that only lacked the model
+fn explain_failures(failures: &[FailedAttempt]) -> String {
+    // With a single connection its own error is already the most precise message
+    if let [only] = failures {
+        return only.message.clone();
+    }
     
-    // Create the request with image
-    let request = GenerationRequest::new(model.clone(), prompt.to_string())
-        .images(vec![image.clone()]);
+    let summary: Vec<String> = failures.iter()
+        .map(|attempt| {
+            let kind = if attempt.local { "local" } else { "remote" };
+            match &attempt.failure {
+                Failure::Unreachable => format!("{} unreachable", kind),
+                Failure::MissingModel(model) => format!("{} missing model '{}'", kind, model),
+                Failure::Other => format!("{} failed: {}", kind, attempt.message),
+            }
+        })
+        .collect();
+    
+    let mut message = format!("All connections failed ({}): {}", client::connection_policy().name(), summary.join(" + "));
+    for attempt in failures {
+        if let Failure::MissingModel(model) = &attempt.failure {
+            let (instance, flag) = if attempt.local { ("local instance", "--local ") } else { ("remote server", "") };
+            message.push_str(&format!("\nHint: model '{}' not found on the {}; run with {}--pull {} to download it", model, instance, flag, model));
+        }
+    }
+    message
+}
+
+// Function to describe one image without streaming, so parallel results don't interleave
+async fn describe_image(ollama: Ollama, model: String, prompt: String, filename: String) -> Result<Metrics, String> {
+    let image = create_image_from_file(&image_path(&filename))
+        .map_err(|e| e.to_string())?;
+    let request = options::apply(GenerationRequest::new(model.clone(), prompt), &model)
+        .images(vec![image]);
     
-    // Start timing
     let start_time = Instant::now();
+    let response = ollama.generate(request).await.map_err(|e| e.to_string())?;
+    
+    let mut metrics = Metrics::new(&model);
+    metrics.request_id = logging::new_request_id();
+    metrics.timestamp = logging::timestamp();
+    metrics.image = Some(filename);
+    metrics.record_final(&response);
+    metrics.finish(start_time.elapsed(), &response.response);
+    Ok(metrics)
+}
+
+// Function to analyze every image in ./images/, at most `concurrency` at a time so a
+// single-GPU server isn't flooded. One failed image doesn't stop the others.
+pub async fn analyze_all_images(concurrency: usize) -> Result<(), Box<dyn std::error::Error>> {
+    settings::load_dotenv();
     
-    // Try to get streaming response, with fallback logic
-    let mut stream = match ollama.generate_stream(request).await {
-        Ok(stream) => stream,
-        Err(e) => {
-            // If remote server failed and we weren't already using local, try local
-            if !use_local && !connection_info.contains("localhost") {
-                println!("❌ Remote server failed: {}", e);
-                println!("🔄 Falling back to local Ollama instance...");
-                
-                let local_ollama = Ollama::new("http://localhost", 11434);
-                let local_request = GenerationRequest::new(model, prompt.to_string())
-                    .images(vec![image]);
-                
-                match local_ollama.generate_stream(local_request).await {
-                    Ok(local_stream) => {
-                        println!("✅ Connected to local Ollama");
-                        local_stream
-                    },
-                    Err(local_e) => {
-                        return Err(format!("Both remote and local connections failed. Remote: {}, Local: {}", e, local_e).into());
-                    }
-                }
-            } else {
-                return Err(e.into());
-            }
-        }
-    };
+    let image_files = list_images()?;
+    if image_files.is_empty() {
+        println!("No images found in ./images/ directory.");
+        println!("Supported formats: {}", SUPPORTED_EXTENSIONS.join(", "));
+        return Ok(());
+    }
     
-    // Handle output
-    let mut stdout = io::stdout();
+    let prompt = prompt::prepare(default_image_prompt());
+    let model = vision_model();
+    let local = client::prefer_local();
+    let (ollama, url) = client::build_client(local)?;
+    warmup::note_used(local, &model);
+    let total = image_files.len();
+    let concurrency = concurrency.max(1);
     
-    println!("\n--- Image Analysis ---");
+    println!("Analyzing {} images on {} with {} ({} at a time)", total, url, model, concurrency);
     
-    // Variables to track metrics
-    let mut total_tokens = 0;
-    let mut response_text = String::new();
-    let mut eval_count = 0;
-    let mut eval_duration = 0;
-    let mut total_duration = 0;
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let completed = Arc::new(AtomicUsize::new(0));
+    let start_time = Instant::now();
     
-    while let Some(res) = stream.next().await {
-        let responses = res.unwrap();
+    let mut tasks = Vec::new();
+    for filename in image_files {
+        let semaphore = semaphore.clone();
+        let completed = completed.clone();
+        let ollama = ollama.clone();
+        let model = model.clone();
+        let prompt = prompt.clone();
         
-        for resp in responses {
-            // Write the response text
-            stdout.write_all(resp.response.as_bytes()).await.unwrap();
-            stdout.flush().await.unwrap();
-            
-            // Collect response text for token counting
-            response_text.push_str(&resp.response);
+        tasks.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let result = describe_image(ollama, model, prompt, filename.clone()).await;
             
-            // If this is the final response, it contains metrics
-            if resp.done {
-                eval_count = resp.eval_count.unwrap_or(0);
-                eval_duration = resp.eval_duration.unwrap_or(0);
-                total_duration = resp.total_duration.unwrap_or(0);
+            let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+            match &result {
+                Ok(metrics) => println!("\n[{}/{}] {}\n{}", done, total, filename, metrics.response.trim()),
+                Err(e) => println!("\n[{}/{}] {} {}: {}", done, total, status::err(), filename, e),
             }
-        }
+            (filename, result)
+        }));
     }
     
-    // Calculate elapsed time
-    let elapsed_time = start_time.elapsed();
-    
-    // Calculate tokens
-    if eval_count > 0 {
-        total_tokens = eval_count;
-    } else {
-        total_tokens = response_text.split_whitespace().count() as u64;
+    let mut succeeded = Vec::new();
+    let mut failed = Vec::new();
+    for task in tasks {
+        match task.await {
+            Ok((_, Ok(metrics))) => succeeded.push(metrics),
+            Ok((filename, Err(e))) => failed.push((filename, e)),
+            Err(e) => failed.push(("(unknown)".to_string(), e.to_string())),
+        }
     }
     
-    // Calculate tokens per second
-    let tokens_per_sec = if elapsed_time.as_secs_f64() > 0.0 {
-        total_tokens as f64 / elapsed_time.as_secs_f64()
-    } else {
-        0.0
-    };
-    
-    // Display metrics
-    println!("\n--- Performance Metrics ---");
-    println!("Image: {}", filename);
-    println!("Connection: {}", if connection_info.contains("localhost") { "Local" } else { "Remote" });
-    println!("Total time: {:.2}s", elapsed_time.as_secs_f64());
-    println!("Tokens generated: {}", total_tokens);
-    println!("Tokens per second: {:.2}", tokens_per_sec);
-    
-    if eval_duration > 0 {
-        let eval_time_sec = eval_duration as f64 / 1_000_000_000.0;
-        let ollama_tokens_per_sec = if eval_time_sec > 0.0 {
-            eval_count as f64 / eval_time_sec
-        } else {
-            0.0
-        };
-        println!("Ollama eval time: {:.2}s", eval_time_sec);
-        println!("Ollama tokens/sec: {:.2}", ollama_tokens_per_sec);
+    println!("\n=== Batch Summary ===");
+    for metrics in &succeeded {
+        println!("{} {}: {} tokens in {:.2}s ({:.1} tok/s)", status::ok(), metrics.image.as_deref().unwrap_or(""), metrics.tokens, metrics.total_time, metrics.tps);
     }
-    
-    println!("----------------------------");
+    for (filename, e) in &failed {
+        println!("{} {}: {}", status::err(), filename, e);
+    }
+    println!("{} succeeded, {} failed in {:.2}s", succeeded.len(), failed.len(), start_time.elapsed().as_secs_f64());
     
     Ok(())
 }
 
-
 // Function to test if vision model is available
 pub async fn test_vision_model() -> Result<(), Box<dyn std::error::Error>> {
-    dotenv::dotenv().ok();
+    settings::load_dotenv();
     
-    let model = std::env::var("vision_model")
-        .unwrap_or_else(|_| "llava".to_string());
+    let model = vision_model();
     
     println!("Testing vision model: {}", model);
     
-    let use_local = should_use_local();
-    let ollama = if use_local {
-        Ollama::new("http://localhost", 11434)
-    } else {
-        let server_ip = std::env::var("server_ip")
-            .expect("server_ip must be set in .env file");
-        let server_url = format!("http://{}", server_ip);
-        Ollama::new(server_url, 11434)
-    };
+    let (ollama, _) = client::build_client(client::prefer_local())?;
     
     // Test with a simple request (no image)
     let request = GenerationRequest::new(model, "Hello".to_string());
     
     match ollama.generate_stream(request).await {
-        Ok(_) => println!("✅ Vision model is available!"),
-        Err(e) => println!("❌ Vision model test failed: {}", e),
+        Ok(_) => println!("{} Vision model is available!", status::ok()),
+        Err(e) => println!("{} Vision model test failed: {}", status::err(), e),
     }
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_selection_accepts_numbers_in_range() {
+        assert_eq!(parse_selection("1", 3), Ok(0));
+        assert_eq!(parse_selection(" 3 ", 3), Ok(2));
+    }
+
+    #[test]
+    fn parse_selection_rejects_zero_and_negative_numbers() {
+        assert_eq!(parse_selection("0", 3), Err("Please enter a number between 1 and 3.".to_string()));
+        assert_eq!(parse_selection("-1", 3), Err("Please enter a number between 1 and 3.".to_string()));
+    }
+
+    #[test]
+    fn parse_selection_rejects_numbers_past_the_end() {
+        assert_eq!(parse_selection("4", 3), Err("Please enter a number between 1 and 3.".to_string()));
+        assert_eq!(parse_selection("99999999999999999999", 3), Err("Please enter a number between 1 and 3.".to_string()));
+    }
+
+    #[test]
+    fn parse_selection_rejects_text() {
+        assert_eq!(parse_selection("two", 3), Err("'two' is not a number. Please enter a number between 1 and 3.".to_string()));
+        assert_eq!(parse_selection("", 3), Err("'' is not a number. Please enter a number between 1 and 3.".to_string()));
+    }
+
+    #[test]
+    fn explain_failures_names_each_connection_and_suggests_a_pull() {
+        let failures = [
+            FailedAttempt { local: false, failure: Failure::Unreachable, message: "Could not reach Ollama".to_string() },
+            FailedAttempt { local: true, failure: Failure::MissingModel("llava".to_string()), message: "Model 'llava' is not installed".to_string() },
+        ];
+
+        assert_eq!(
+            explain_failures(&failures),
+            "All connections failed (remote-first): remote unreachable + local missing model 'llava'\n\
+             Hint: model 'llava' not found on the local instance; run with --local --pull llava to download it"
+        );
+    }
+
+    #[test]
+    fn explain_failures_keeps_a_single_message() {
+        let failures = [FailedAttempt { local: true, failure: Failure::Other, message: "timed out".to_string() }];
+        assert_eq!(explain_failures(&failures), "timed out");
+    }
+
+    // A scratch images directory per test, so tests don't touch ./images/
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ollama-client-test-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn image_path_splits_backslashes_when_no_file_matches() {
+        let dir = scratch_dir("backslash");
+        assert_eq!(image_path_in(&dir, r"cats\tabby.jpg"), dir.join("cats").join("tabby.jpg"));
+        assert_eq!(image_path_in(&dir, r".\images\cat.png"), dir.join("cat.png"));
+        assert_eq!(image_path_in(&dir, "cats/tabby.jpg"), dir.join("cats").join("tabby.jpg"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn image_path_keeps_a_backslash_that_is_part_of_a_file_name() {
+        let dir = scratch_dir("literal");
+        fs::write(dir.join(r"odd\name.png"), b"").unwrap();
+        assert_eq!(image_path_in(&dir, r"odd\name.png"), dir.join(r"odd\name.png"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn image_path_handles_windows_paths() {
+        let dir = PathBuf::from(r".\images");
+        assert_eq!(image_path_in(&dir, r"cats\tabby.jpg"), dir.join("cats").join("tabby.jpg"));
+        assert_eq!(image_path_in(&dir, r"C:\Users\me\Pictures\cat.png"), PathBuf::from(r"C:\Users\me\Pictures\cat.png"));
+        assert_eq!(image_name(&PathBuf::from(r"cats\tabby.jpg")), "cats/tabby.jpg");
+    }
+
+    #[test]
+    fn image_name_uses_forward_slashes() {
+        assert_eq!(image_name(&Path::new("cats").join("tabby.jpg")), "cats/tabby.jpg");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_images_are_listed_when_the_target_is_an_image() {
+        let dir = scratch_dir("symlinks");
+        let library = scratch_dir("symlinks-library");
+        fs::write(library.join("cat.png"), b"\x89PNG\r\n\x1a\n....").unwrap();
+        fs::write(library.join("notes.txt"), b"not an image").unwrap();
+        fs::write(dir.join("plain.png"), b"").unwrap();
+        for (target, link) in [("cat.png", "linked.png"), ("notes.txt", "fake.png"), ("missing.png", "broken.png")] {
+            let _ = fs::remove_file(dir.join(link));
+            std::os::unix::fs::symlink(library.join(target), dir.join(link)).unwrap();
+        }
+        let _ = fs::remove_file(dir.join("library"));
+        std::os::unix::fs::symlink(&library, dir.join("library")).unwrap();
+
+        let mut images = Vec::new();
+        collect_images(&dir, Path::new(""), &mut HashSet::new(), &mut images).unwrap();
+        images.sort();
+        assert_eq!(images, ["linked.png", "plain.png"]);
+    }
+
+    #[test]
+    fn every_supported_extension_has_a_signature() {
+        let mut covered: Vec<&str> = IMAGE_SIGNATURES.iter()
+            .flat_map(|signature| signature.extensions.iter().copied())
+            .collect();
+        let mut supported = SUPPORTED_EXTENSIONS.to_vec();
+        covered.sort_unstable();
+        covered.dedup();
+        supported.sort_unstable();
+        assert_eq!(covered, supported);
+    }
+
+    #[test]
+    fn detect_image_format_reads_magic_bytes() {
+        assert_eq!(detect_image_format(b"\x89PNG\r\n\x1a\n...."), Some("png"));
+        assert_eq!(detect_image_format(&[0xFF, 0xD8, 0xFF, 0xE0]), Some("jpg"));
+        assert_eq!(detect_image_format(b"GIF87a...."), Some("gif"));
+        assert_eq!(detect_image_format(b"GIF89a...."), Some("gif"));
+        assert_eq!(detect_image_format(b"BM......"), Some("bmp"));
+        assert_eq!(detect_image_format(b"RIFF\x24\0\0\0WEBPVP8 "), Some("webp"));
+    }
+
+    #[test]
+    fn detect_image_format_rejects_other_data() {
+        assert_eq!(detect_image_format(b""), None);
+        assert_eq!(detect_image_format(b"GIF88a"), None);
+        assert_eq!(detect_image_format(b"RIFF\0\0\0\0WAVE"), None);
+        assert_eq!(detect_image_format(b"RIFF"), None);
+        assert_eq!(detect_image_format(b"%PDF-1.7"), None);
+    }
+}
@@ -0,0 +1,100 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::error::ClientError;
+use crate::{client, error, settings, status};
+
+// How many /api/show requests run at once, so a large library doesn't flood the server
+const DETAIL_CONCURRENCY: usize = 4;
+
+// Function to fetch a model's details via /api/show: family, parameter size and quantization.
+// ollama-rs doesn't expose the "details" object, so the raw endpoint is used.
+async fn model_details(http_client: reqwest::Client, base_url: String, model: String) -> Result<Value, String> {
+    let response = http_client
+        .post(format!("{}/api/show", base_url))
+        .json(&json!({ "model": model }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let info: Value = error::check_response(response)
+        .await
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(info.get("details").cloned().unwrap_or(Value::Null))
+}
+
+// Function to handle --export-models: list the installed models, fetch each one's details
+// (a few at a time) and write one JSON inventory. A model whose details can't be read is
+// still listed, with an "error" field, instead of aborting the export.
+pub async fn export_models(path: &str, local: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (ollama, url) = client::build_client(local)?;
+    let (http_client, base_url) = client::build_http_client(local)?;
+    println!("Server: {}", url);
+
+    let models = ollama.list_local_models().await?;
+    if models.is_empty() {
+        println!("{} {}", status::warn(), ClientError::NoModelsInstalled { model: settings::model_for(local) });
+    }
+    println!("Exporting {} models...", models.len());
+
+    let semaphore = Arc::new(Semaphore::new(DETAIL_CONCURRENCY));
+    let mut tasks = Vec::new();
+    for model in &models {
+        let semaphore = semaphore.clone();
+        let http_client = http_client.clone();
+        let base_url = base_url.clone();
+        let name = model.name.clone();
+
+        tasks.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            model_details(http_client, base_url, name).await
+        }));
+    }
+
+    let mut entries = Vec::new();
+    let mut failed = 0;
+    for (model, task) in models.iter().zip(tasks) {
+        let mut entry = json!({
+            "name": model.name,
+            "size": model.size,
+            "modified_at": model.modified_at,
+        });
+
+        match task.await.map_err(|e| e.to_string()).and_then(|result| result) {
+            Ok(details) => {
+                entry["family"] = details.get("family").cloned().unwrap_or(Value::Null);
+                entry["families"] = details.get("families").cloned().unwrap_or(Value::Null);
+                entry["parameter_size"] = details.get("parameter_size").cloned().unwrap_or(Value::Null);
+                entry["quantization"] = details.get("quantization_level").cloned().unwrap_or(Value::Null);
+                entry["format"] = details.get("format").cloned().unwrap_or(Value::Null);
+            },
+            Err(e) => {
+                println!("{} Could not read details of {}: {}", status::warn(), model.name, e);
+                entry["error"] = Value::String(e);
+                failed += 1;
+            }
+        }
+        entries.push(entry);
+    }
+
+    let inventory = json!({
+        "server": url,
+        "count": entries.len(),
+        "models": entries,
+    });
+
+    fs::write(path, serde_json::to_string_pretty(&inventory)?)
+        .map_err(|e| format!("Could not write {}: {}", path, e))?;
+
+    if failed > 0 {
+        println!("{} Exported {} models to {} ({} without details)", status::warn(), models.len(), path, failed);
+    } else {
+        println!("{} Exported {} models to {}", status::ok(), models.len(), path);
+    }
+    Ok(())
+}
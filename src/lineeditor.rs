@@ -0,0 +1,170 @@
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use std::collections::VecDeque;
+use std::io::{stdin, stdout, IsTerminal, Write};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+// Prompt history is kept next to .env so it survives between runs
+const HISTORY_FILE: &str = ".ollama_history";
+
+// One editor for the whole process, created on first use; None when the terminal doesn't
+// support line editing. Input is read from tokio tasks that move between worker threads,
+// so an editor per thread would each keep (and save) only part of the history.
+static EDITOR: LazyLock<Mutex<Option<DefaultEditor>>> = LazyLock::new(|| Mutex::new(open_editor()));
+
+// Lines of a paste the user chose to send one at a time, handed out before reading stdin.
+// Process-wide for the same reason as the editor.
+static QUEUED: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+// Function to create the line editor and load the saved history
+fn open_editor() -> Option<DefaultEditor> {
+    if !stdin().is_terminal() {
+        return None;
+    }
+
+    let mut editor = DefaultEditor::new().ok()?;
+    // A missing history file just means this is the first run
+    let _ = editor.load_history(HISTORY_FILE);
+    Some(editor)
+}
+
+// Typing this alone starts multiline input, ended by a line containing only EOF
+const MULTILINE_COMMAND: &str = "/multiline";
+const MULTILINE_END: &str = "EOF";
+
+// Function to read input with arrow-key editing, history and Ctrl-R search.
+// A line ending in `\` continues on the next line, and /multiline reads until EOF,
+// so code and multi-paragraph prompts can be pasted. Falls back to a plain read when input is piped.
+// Returns None once input has ended (stdin closed, Ctrl-D or Ctrl-C) so callers can stop cleanly.
+pub fn read_line(prompt: &str) -> Option<String> {
+    let first = read_raw(prompt)?;
+
+    let input = if first.trim() == MULTILINE_COMMAND {
+        println!("(multiline input: finish with a line containing only {})", MULTILINE_END);
+        let mut lines = Vec::new();
+        loop {
+            match read_raw("... ") {
+                Some(line) if line.trim() != MULTILINE_END => lines.push(line),
+                _ => break,
+            }
+        }
+        lines.join("\n")
+    } else {
+        let mut input = confirm_paste(first);
+        while input.trim_end().ends_with('\\') {
+            input = input.trim_end().trim_end_matches('\\').to_string();
+            match read_raw("... ") {
+                Some(line) => {
+                    input.push('\n');
+                    input.push_str(&line);
+                },
+                None => break,
+            }
+        }
+        input
+    };
+
+    let input = input.trim().to_string();
+    if !input.is_empty() {
+        add_history(&input);
+    }
+    Some(input)
+}
+
+// Function to catch a multi-line paste into a single-line prompt, which would otherwise leak
+// the remaining lines into the next prompts. Asks whether to send the lines as one prompt;
+// if not, the first line is used now and the rest are queued as separate inputs.
+fn confirm_paste(first: String) -> String {
+    // Lines still queued from the last paste were already confirmed
+    if !stdin().is_terminal() || !queued().is_empty() {
+        return first;
+    }
+
+    // A bracketed paste arrives as one line with breaks in it; otherwise the
+    // remaining lines are still waiting on stdin
+    let mut lines: Vec<String> = first.split('\n').map(str::to_string).collect();
+    while input_pending() {
+        match read_raw("") {
+            Some(line) => lines.extend(line.split('\n').map(str::to_string)),
+            None => break,
+        }
+    }
+    while lines.len() > 1 && lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    if lines.len() <= 1 {
+        return lines.join("\n");
+    }
+
+    let answer = read_raw(&format!("Received {} lines — treat as one prompt? (Y/n) ", lines.len()))
+        .unwrap_or_default();
+    if matches!(answer.trim().to_lowercase().as_str(), "n" | "no") {
+        println!("(tip: type {} to paste several lines as one prompt)", MULTILINE_COMMAND);
+        let first = lines.remove(0);
+        queued().extend(lines);
+        return first;
+    }
+    lines.join("\n")
+}
+
+// Function to tell whether more input is already waiting, i.e. the rest of a paste.
+// The short wait catches lines the terminal is still sending.
+#[cfg(unix)]
+fn input_pending() -> bool {
+    let mut fd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+    // SAFETY: polls a single valid pollfd for at most 20ms
+    unsafe { libc::poll(&mut fd, 1, 20) > 0 }
+}
+
+// Without poll(), only bracketed pastes (one line with breaks in it) are detected
+#[cfg(not(unix))]
+fn input_pending() -> bool {
+    false
+}
+
+// Function to read one raw line, keeping indentation (only the line break is removed).
+// Queued lines from a paste come first.
+// Returns None at end of input or when the user presses Ctrl-C / Ctrl-D.
+fn read_raw(prompt: &str) -> Option<String> {
+    let next = queued().pop_front();
+    if let Some(line) = next {
+        println!("{}{}", prompt, line);
+        return Some(line);
+    }
+
+    let edited = editor().as_mut().and_then(|editor| match editor.readline(prompt) {
+        Ok(line) => Some(Some(line)),
+        Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => Some(None),
+        Err(_) => None,
+    });
+
+    edited.unwrap_or_else(|| read_plain_line(prompt))
+}
+
+// Function to remember an entry and save the history file
+fn add_history(entry: &str) {
+    if let Some(editor) = editor().as_mut() {
+        let _ = editor.add_history_entry(entry);
+        let _ = editor.save_history(HISTORY_FILE);
+    }
+}
+
+fn editor() -> MutexGuard<'static, Option<DefaultEditor>> {
+    EDITOR.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn queued() -> MutexGuard<'static, VecDeque<String>> {
+    QUEUED.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Function to read a line without editing support; read errors are treated like end of input
+fn read_plain_line(prompt: &str) -> Option<String> {
+    print!("{}", prompt);
+    let _ = stdout().flush();
+
+    let mut input = String::new();
+    match stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input.trim_end_matches(['\n', '\r']).to_string()),
+    }
+}
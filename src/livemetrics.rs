@@ -0,0 +1,74 @@
+use std::io::{stderr, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use crate::settings;
+
+// How often the status line is redrawn
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Running token count and speed for --live-metrics, drawn on stderr while a long answer streams.
+// The server only reports the exact count (eval_count) in its final message, so the live figure
+// is approximate: Ollama streams about one token per chunk, and chunks are what's counted.
+// When the answer itself is on the terminal the status sits after the text and is erased
+// before more text is written; otherwise it's a single line overwritten with \r.
+pub struct LiveMetrics {
+    inline: bool,
+    first_token: Instant,
+    last_update: Instant,
+    shown: bool,
+}
+
+impl LiveMetrics {
+    // Function to start the live status, or None without --live-metrics or when stderr isn't a terminal.
+    // `inline` says the response text is being written to the same terminal.
+    pub fn new(inline: bool) -> Option<Self> {
+        if !settings::get().live_metrics || !stderr().is_terminal() {
+            return None;
+        }
+
+        let now = Instant::now();
+        Some(LiveMetrics { inline, first_token: now, last_update: now, shown: false })
+    }
+
+    // Function to tell whether a second has passed since the last redraw
+    pub fn due(&self) -> bool {
+        self.last_update.elapsed() >= UPDATE_INTERVAL
+    }
+
+    // Function to draw the status with the chunks received so far, one token each
+    pub fn show(&mut self, chunks: usize) {
+        self.clear();
+
+        let elapsed = self.first_token.elapsed().as_secs_f64();
+        let tps = if elapsed > 0.0 { chunks as f64 / elapsed } else { 0.0 };
+        let status = format!("[~{} tokens, {:.1} tok/s]", chunks, tps);
+
+        let mut stderr = stderr();
+        if self.inline {
+            // Remember where the text ends so the status can be erased from there
+            let _ = write!(stderr, "\x1b7 {}", status);
+        } else {
+            let _ = write!(stderr, "\r{}", status);
+        }
+        let _ = stderr.flush();
+
+        self.shown = true;
+        self.last_update = Instant::now();
+    }
+
+    // Function to erase the status so text or the final metrics can be written (safe to call repeatedly)
+    pub fn clear(&mut self) {
+        if !self.shown {
+            return;
+        }
+
+        let mut stderr = stderr();
+        if self.inline {
+            let _ = write!(stderr, "\x1b8\x1b[J");
+        } else {
+            let _ = write!(stderr, "\r\x1b[K");
+        }
+        let _ = stderr.flush();
+        self.shown = false;
+    }
+}
@@ -0,0 +1,44 @@
+use std::io::{stderr, IsTerminal, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+// A request that produces nothing for this long is most likely waiting for the model to load
+const LOADING_THRESHOLD: Duration = Duration::from_secs(1);
+
+static TIMER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+static SHOWN: AtomicBool = AtomicBool::new(false);
+
+// Function to show "Loading model into memory..." if no output arrives within the threshold.
+// Written to stderr on terminals only, so piped output stays clean.
+pub fn start() {
+    if !stderr().is_terminal() {
+        return;
+    }
+
+    let timer = tokio::spawn(async {
+        tokio::time::sleep(LOADING_THRESHOLD).await;
+        let mut stderr = stderr();
+        let _ = write!(stderr, "Loading model into memory...");
+        let _ = stderr.flush();
+        SHOWN.store(true, Ordering::SeqCst);
+    });
+
+    if let Some(previous) = TIMER.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).replace(timer) {
+        previous.abort();
+    }
+}
+
+// Function to cancel the indicator and clear its line once tokens start flowing (safe to call repeatedly)
+pub fn stop() {
+    if let Some(timer) = TIMER.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take() {
+        timer.abort();
+    }
+
+    if SHOWN.swap(false, Ordering::SeqCst) {
+        let mut stderr = stderr();
+        let _ = write!(stderr, "\r\x1b[K");
+        let _ = stderr.flush();
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::settings;
+
+static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Function to print a message only when --verbose is set (to stderr, so piped output stays clean)
+pub fn debug(message: &str) {
+    if settings::get().verbose {
+        eprintln!("[debug] {}", message);
+    }
+}
+
+// Function to print a status line; it goes to stderr when stdout carries machine-readable output
+pub fn info(message: &str) {
+    if settings::get().stream_json {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+// Function to create a short random ID so a specific generation can be referenced in bug reports
+pub fn new_request_id() -> String {
+    // RandomState is seeded randomly per process; the counter keeps IDs unique within a run
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed));
+    format!("{:08x}", hasher.finish() as u32)
+}
+
+// Function to start a generation: assign a request ID and log the prompt under it
+pub fn start_request(prompt: &str) -> String {
+    let request_id = new_request_id();
+    debug(&format!("request {} at {} prompt: {}", request_id, timestamp(), prompt));
+
+    // --echo-prompt makes transcripts self-documenting
+    if settings::get().echo_prompt {
+        let quoted: Vec<String> = prompt.lines().map(|line| format!("> {}", line)).collect();
+        info(&quoted.join("\n"));
+    }
+
+    request_id
+}
+
+// Function to format the current time as "YYYY-MM-DD HH:MM:SS UTC" without extra dependencies
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let days = (secs / 86_400) as i64;
+    let seconds_of_day = secs % 86_400;
+
+    // Convert days since epoch to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        (seconds_of_day % 3_600) / 60,
+        seconds_of_day % 60
+    )
+}
@@ -0,0 +1,43 @@
+use std::collections::HashSet;
+
+// Watches streamed text for a model stuck repeating itself, e.g. the same sentence
+// over and over. Only the most recent characters are kept (a rolling window).
+const MIN_UNIT: usize = 8;
+const MAX_UNIT: usize = 200;
+const MIN_REPEATS: usize = 4;
+
+#[derive(Default)]
+pub struct LoopDetector {
+    window: Vec<char>,
+}
+
+impl LoopDetector {
+    // Function to feed streamed text; returns the repeating phrase once a loop is detected
+    pub fn push(&mut self, chunk: &str) -> Option<String> {
+        self.window.extend(chunk.chars());
+
+        let capacity = MAX_UNIT * MIN_REPEATS;
+        if self.window.len() > capacity {
+            self.window.drain(..self.window.len() - capacity);
+        }
+
+        for unit in MIN_UNIT..=MAX_UNIT {
+            let needed = unit * MIN_REPEATS;
+            if needed > self.window.len() {
+                break;
+            }
+
+            // The tail is a loop if every character equals the one `unit` positions earlier
+            let tail = &self.window[self.window.len() - needed..];
+            if (unit..needed).all(|i| tail[i] == tail[i - unit]) {
+                let phrase: String = tail[needed - unit..].iter().collect();
+                // Ignore runs of whitespace or a single repeated character
+                if phrase.trim().chars().collect::<HashSet<_>>().len() > 1 {
+                    return Some(phrase);
+                }
+            }
+        }
+
+        None
+    }
+}
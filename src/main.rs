@@ -1,11 +1,53 @@
 // Import required dependencies
 use clap::Parser;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
+use std::time::Duration;
 
 // Import our custom modules
 mod connecttoollama;
 mod connectlocally;
+mod answer;
 mod imagedescriber;  // Add this new import
+mod tools;
+mod metrics;
+mod modelmanager;
+mod settings;
+mod streaming;
+mod wordwrap;
+mod prompt;
+mod client;
+mod warmup;
+mod status;
+mod logging;
+mod profiles;
+mod error;
+mod loopdetect;
+mod codenumber;
+mod lineeditor;
+mod tokencount;
+mod diagnose;
+mod clipboard;
+mod openai;
+mod wsserver;
+mod templates;
+mod options;
+mod sseserver;
+mod candidates;
+mod summarize;
+mod export;
+mod ansistrip;
+mod loading;
+mod tasks;
+mod pager;
+mod params;
+mod hooks;
+mod inventory;
+mod livemetrics;
+mod sessions;
+mod watchqueue;
+mod compare;
+mod critique;
+mod repl;
 
 #[derive(Parser)]
 #[command(name = "Ollama Client")]
@@ -15,6 +57,18 @@ struct Args {
     #[arg(short, long)]
     prompt: Option<String>,
     
+    /// Read the prompt from a file (files over 1MB need --force)
+    #[arg(long, value_name = "PATH", conflicts_with = "prompt")]
+    prompt_file: Option<String>,
+    
+    /// The prompt as plain words, e.g. `summarize this text` (same as --prompt)
+    #[arg(value_name = "PROMPT", conflicts_with_all = ["prompt", "prompt_file"])]
+    words: Vec<String>,
+    
+    /// Send files larger than 1MB as prompts or context anyway
+    #[arg(long)]
+    force: bool,
+    
     /// Skip the menu and test connection
     #[arg(short, long)]
     test: bool,
@@ -23,9 +77,341 @@ struct Args {
     #[arg(short, long)]
     local: bool,
     
-    /// Analyze an image (specify image filename)
+    /// Analyze an image (a filename in ./images/, an http(s) URL, or - for image bytes on stdin)
     #[arg(short, long)]
     image: Option<String>,
+    
+    /// Largest base64-encoded image to send, in bytes; bigger images fail before the request is made
+    #[arg(long, value_name = "BYTES", default_value_t = 20 * 1024 * 1024)]
+    max_image_bytes: u64,
+    
+    /// Only count the prompt's tokens (uses --prompt if given), without generating
+    #[arg(long)]
+    count_tokens: bool,
+    
+    /// Print machine-readable JSON where supported (--count-tokens)
+    #[arg(long)]
+    json: bool,
+    
+    /// Download a model to the server (--local for the local instance)
+    #[arg(long, value_name = "MODEL")]
+    pull: Option<String>,
+    
+    /// Write a JSON inventory of the installed models (sizes, families, quantization) to a file
+    #[arg(long, value_name = "PATH")]
+    export_models: Option<String>,
+    
+    /// Print diagnostics for bug reports as JSON (secrets redacted), optionally also to a file
+    #[arg(long, value_name = "PATH")]
+    diagnose: Option<Option<String>>,
+    
+    /// Analyze the image on the clipboard, e.g. a screenshot (uses --image-prompt if given)
+    #[arg(long, conflicts_with = "image")]
+    image_clipboard: bool,
+    
+    /// Keep a copy of images downloaded with --image <URL> in ./images/
+    #[arg(long)]
+    save_fetched: bool,
+    
+    /// Analyze every image in ./images/ with the default image prompt
+    #[arg(long)]
+    all_images: bool,
+    
+    /// How many images --all-images sends to the server at once
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    image_concurrency: usize,
+    
+    /// Send the prompt through the OpenAI-compatible /v1/chat/completions endpoint
+    #[arg(long)]
+    openai_compat: bool,
+    
+    /// Run the prompt on the remote server and then the local instance, and compare their speed
+    #[arg(long)]
+    compare_local_remote: bool,
+    
+    /// Serve generations to websocket clients on this address, e.g. 127.0.0.1:8080
+    #[arg(long, value_name = "ADDR")]
+    serve_ws: Option<String>,
+    
+    /// Serve generations as Server-Sent Events on this address, e.g. 127.0.0.1:8080
+    #[arg(long, value_name = "ADDR")]
+    sse: Option<String>,
+    
+    /// Generate N alternative answers to the prompt, each with its own seed
+    #[arg(long, value_name = "N")]
+    candidates: Option<u32>,
+    
+    /// Summarize a large text file by chunking it to fit the context window
+    #[arg(long, value_name = "FILE")]
+    summarize: Option<String>,
+    
+    /// Start an interactive session that keeps conversation context
+    #[arg(long)]
+    repl: bool,
+    
+    /// Answer every prompt file (.txt, .prompt) dropped into DIR, writing <name>.response.md next to it
+    #[arg(long, value_name = "DIR")]
+    watch_queue: Option<String>,
+    
+    /// Save the interactive session as ./sessions/NAME.json and resume it next time
+    #[arg(long, value_name = "NAME")]
+    session: Option<String>,
+    
+    /// List the saved sessions tagged with TAG (tag them with /tag in a session)
+    #[arg(long, value_name = "TAG")]
+    sessions_by_tag: Option<String>,
+    
+    /// Let a prompt starting with @model:<name> use that model for that request only
+    #[arg(long)]
+    inline_directives: bool,
+    
+    /// Run the tool-calling demo (uses --prompt if given)
+    #[arg(long)]
+    tools: bool,
+    
+    /// Show a bar of where the time went: model loading, prompt evaluation, generation
+    #[arg(long)]
+    timing_bar: bool,
+    
+    /// Custom metrics line, e.g. "{model}: {tps:.1} tok/s in {total_time:.2}s"
+    #[arg(long)]
+    metrics_format: Option<String>,
+    
+    /// Word-wrap streamed output at this many columns (terminal output only)
+    #[arg(long, value_name = "COLS")]
+    wrap: Option<usize>,
+    
+    /// Text added before every prompt (overrides prompt_prefix in .env)
+    #[arg(long)]
+    prompt_prefix: Option<String>,
+    
+    /// Text added after every prompt, e.g. "Respond in JSON." (overrides prompt_suffix in .env)
+    #[arg(long)]
+    prompt_suffix: Option<String>,
+    
+    /// Show the model, server and final prompt without sending anything
+    #[arg(long)]
+    dry_run: bool,
+    
+    /// Substitute ${VAR} references in prompts from the environment
+    #[arg(long)]
+    expand_env: bool,
+    
+    /// Preload the model into memory and report the load time
+    #[arg(long)]
+    warmup: bool,
+    
+    /// How long the server keeps the model loaded: -1 (forever), 0, or e.g. 30s, 10m, 2h
+    #[arg(long, value_name = "DURATION", allow_hyphen_values = true, value_parser = options::validate_keep_alive)]
+    keep_alive: Option<String>,
+    
+    /// Default prompt for image analysis (overrides image_prompt in .env)
+    #[arg(long)]
+    image_prompt: Option<String>,
+    
+    /// Use a named prompt template for image analysis (see --template-list)
+    #[arg(long, value_name = "NAME")]
+    image_template: Option<String>,
+    
+    /// List the available prompt templates
+    #[arg(long)]
+    template_list: bool,
+    
+    /// Use plain ASCII status markers instead of emoji (also ASCII_ONLY=1)
+    #[arg(long)]
+    ascii: bool,
+    
+    /// Print debug details (request IDs, timings) to stderr
+    #[arg(short, long)]
+    verbose: bool,
+    
+    /// Use a named connection profile from profiles.toml
+    #[arg(long)]
+    profile: Option<String>,
+    
+    /// List the profiles defined in profiles.toml
+    #[arg(long)]
+    list_profiles: bool,
+    
+    /// Which server to use and whether to fall back (overrides connection_policy in .env)
+    #[arg(long, value_enum)]
+    policy: Option<client::ConnectionPolicy>,
+    
+    /// Ignore .env and config environment variables; use only flags and built-in defaults
+    #[arg(long, conflicts_with = "env_files")]
+    no_env: bool,
+    
+    /// Remote server host (overrides the profile and server_ip)
+    #[arg(long)]
+    host: Option<String>,
+    
+    /// Model and remote server in one, e.g. mistral@192.168.1.5:11434 (--model, --host and --port override its parts)
+    #[arg(long, value_name = "MODEL@HOST[:PORT]", value_parser = client::parse_connect)]
+    connect: Option<client::ConnectTarget>,
+    
+    /// Remote server port (overrides the profile; default 11434, or 443 with https)
+    #[arg(long)]
+    port: Option<u16>,
+    
+    /// Connect to the remote server over https
+    #[arg(long)]
+    https: bool,
+    
+    /// Model to use (overrides the profile and model in .env)
+    #[arg(short, long)]
+    model: Option<String>,
+    
+    /// Search symlinked folders in ./images/ (symlinked image files are always listed)
+    #[arg(long)]
+    follow_symlinks: bool,
+    
+    /// Emit one JSON object per streamed chunk, then a final object with metrics
+    #[arg(long)]
+    stream_json: bool,
+    
+    /// List the supported image formats
+    #[arg(long)]
+    list_formats: bool,
+    
+    /// Warn when the model starts repeating the same phrase
+    #[arg(long)]
+    detect_loops: bool,
+    
+    /// Stop the stream when a repetition loop is detected (implies --detect-loops)
+    #[arg(long)]
+    abort_on_loop: bool,
+    
+    /// Extra HTTP header for every request, e.g. "X-Api-Key: secret" (repeatable)
+    #[arg(long = "header", value_name = "KEY: VALUE", value_parser = client::parse_header)]
+    headers: Vec<(String, String)>,
+    
+    /// Open the finished response in $PAGER (or less) instead of streaming it (terminal output only)
+    #[arg(long)]
+    pager: bool,
+    
+    /// Flush output in batches instead of per token (faster when piping large responses)
+    #[arg(long)]
+    buffered: bool,
+    
+    /// Remove ANSI escape codes (colors, cursor movement) from the model's output
+    #[arg(long)]
+    strip_ansi: bool,
+    
+    /// Number the lines inside fenced code blocks (terminal output only)
+    #[arg(long)]
+    number_code: bool,
+    
+    /// Load variables from this file before .env (repeatable; later files override earlier ones)
+    #[arg(long = "env-file", value_name = "PATH")]
+    env_files: Vec<String>,
+    
+    /// Copy the full response to the clipboard when it finishes
+    #[arg(long)]
+    clipboard: bool,
+    
+    /// Also write the finished response to this file
+    #[arg(long, value_name = "PATH")]
+    output: Option<String>,
+    
+    /// Pipe the finished response into a shell command and print its output, e.g. "jq ."
+    #[arg(long, value_name = "COMMAND")]
+    post_hook: Option<String>,
+    
+    /// Pipe the finished response into this executable script and print its output
+    #[arg(long, value_name = "PATH", conflicts_with = "post_hook")]
+    post_hook_file: Option<String>,
+    
+    /// Save the final response object (context, durations, counts) as pretty JSON
+    #[arg(long, value_name = "PATH")]
+    save_response_json: Option<String>,
+    
+    /// Format used for --output; markdown-to-html renders the response as HTML
+    #[arg(long, value_enum, value_name = "FORMAT", requires = "output")]
+    format_response: Option<export::ResponseFormat>,
+    
+    /// Don't print the response text (useful with --clipboard)
+    #[arg(short, long)]
+    quiet: bool,
+    
+    /// Warn when a prompt is longer than the model's context window
+    #[arg(long)]
+    check_context: bool,
+    
+    /// Context window size to request (overrides model_defaults.toml; also used by --check-context)
+    #[arg(long, value_name = "TOKENS")]
+    num_ctx: Option<u64>,
+    
+    /// Sampling temperature (overrides model_defaults.toml)
+    #[arg(long)]
+    temperature: Option<f32>,
+    
+    /// Named bundle of sampling options: creative, precise, code, or one from presets.toml
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+    
+    /// System prompt (overrides model_defaults.toml)
+    #[arg(long)]
+    system: Option<String>,
+    
+    /// After a generation, show the parameters used: requested values vs the model's defaults
+    #[arg(long)]
+    show_params: bool,
+    
+    /// Ask the model to answer in this language, e.g. fr, de, ja
+    #[arg(long, value_name = "CODE")]
+    lang: Option<String>,
+    
+    /// With --test or --diagnose, keep polling the server for up to this many seconds until it answers
+    #[arg(long, value_name = "SECONDS")]
+    wait_for_server: Option<u64>,
+    
+    /// Probe the server once and exit: silent on success, one line on stderr and a non-zero code on failure
+    #[arg(long)]
+    healthcheck: bool,
+    
+    /// Seconds --healthcheck waits for an answer before failing
+    #[arg(long, value_name = "SECONDS", default_value_t = 5)]
+    timeout: u64,
+    
+    /// After the answer, ask the model to rate and critique it (a second request, so off by default)
+    #[arg(long)]
+    self_critique: bool,
+    
+    /// Show the approximate running token count and tokens/sec on a status line while the response streams
+    #[arg(long)]
+    live_metrics: bool,
+    
+    /// Stop generating this many seconds after the first token and keep what was generated so far
+    #[arg(long, value_name = "SECONDS")]
+    max_gen_time: Option<u64>,
+    
+    /// Stop the response as soon as the output matches this regular expression
+    #[arg(long, value_name = "PATTERN", value_parser = streaming::validate_stop_regex)]
+    stop_regex: Option<String>,
+    
+    /// Re-send a failed request up to N times (timeouts and connection errors only)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    retries: u32,
+    
+    /// Re-send the request up to N times when the model returns an empty response
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    retry_on_empty: u32,
+    
+    /// Include a text file in the prompt (repeatable)
+    #[arg(long = "context-file", value_name = "PATH")]
+    context_files: Vec<String>,
+    
+    /// Print the submitted prompt (prefixed with "> ") before the response
+    #[arg(long)]
+    echo_prompt: bool,
+    
+    /// Unload the model from memory when the program exits
+    #[arg(long)]
+    unload_on_exit: bool,
+    
+    /// Debug: write the base64 image payload sent to the server to this file (can be large)
+    #[arg(long, hide = true, value_name = "PATH")]
+    dump_image_payload: Option<String>,
 }
 
 fn display_menu() {
@@ -36,117 +422,558 @@ fn display_menu() {
     println!("4. Test Local Connection");
     println!("5. View Configuration");
     println!("6. Analyze Image");
-    println!("7. Exit");
-    print!("Choose an option (1-7): ");
+    println!("7. Tool Calling Demo");
+    println!("8. Warm Up Model");
+    println!("9. Interactive Session");
+    println!("10. Background Tasks");
+    println!("11. Exit");
+    print!("Choose an option (1-11): ");
     io::stdout().flush().unwrap();
 }
 
-fn get_user_choice() -> String {
+// Function to read the menu choice; None when stdin has closed
+fn get_user_choice() -> Option<String> {
     let mut input = String::new();
-    io::stdin().read_line(&mut input).expect("Failed to read input");
-    input.trim().to_string()
+    match io::stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input.trim().to_string()),
+    }
 }
 
 fn display_config() {
-    dotenv::dotenv().ok();
+    settings::load_dotenv();
     
     println!("\n=== Current Configuration ===");
     
-    match std::env::var("server_ip") {
+    match settings::env_var("server_ip") {
         Ok(ip) => println!("Remote Server IP: {}", ip),
         Err(_) => println!("Remote Server IP: Not set in .env file"),
     }
     
-    match std::env::var("model") {
-        Ok(model) => println!("Model: {}", model),
-        Err(_) => println!("Model: llama3.2 (default)"),
+    println!("Local Model: {}", settings::model_for(true));
+    println!("Remote Model: {}", settings::model_for(false));
+    
+    // Highest priority first: the process environment always wins over files
+    let env_files = &settings::get().env_files;
+    if !env_files.is_empty() {
+        let mut layers = vec!["environment".to_string()];
+        layers.extend(env_files.iter().rev().cloned());
+        layers.push(".env".to_string());
+        println!("Env Files: {} (first match wins)", layers.join(" > "));
+    }
+    
+    if let Some(name) = &settings::get().profile_name {
+        println!("Profile: {} (from profiles.toml)", name);
     }
     
+    println!("Connection Policy: {}", client::connection_policy().name());
     println!("Local Server: http://localhost:11434");
     println!("Images Directory: ./images/");
     println!("================================");
 }
 
+// Default listen address for servers started from the menu
+const DEFAULT_SERVE_ADDR: &str = "127.0.0.1:8080";
+
+// Function to start, list and stop background tasks from the menu
+fn background_tasks_menu() {
+    println!("\n=== Background Tasks ===");
+    let running = tasks::running();
+    if running.is_empty() {
+        println!("Nothing running in the background.");
+    } else {
+        for name in &running {
+            println!("  - {}", name);
+        }
+    }
+    
+    println!("1. Start WebSocket Server");
+    println!("2. Start SSE Server");
+    println!("3. Warm Up Model");
+    println!("4. Stop All Background Tasks");
+    println!("5. Back");
+    print!("Choose an option (1-5): ");
+    io::stdout().flush().unwrap();
+    
+    let Some(choice) = get_user_choice() else {
+        return;
+    };
+    
+    let local = client::prefer_local();
+    match choice.as_str() {
+        "1" | "2" => {
+            let prompt = format!("Listen address [{}]: ", DEFAULT_SERVE_ADDR);
+            let Some(addr) = connecttoollama::get_user_input(&prompt) else {
+                return;
+            };
+            let addr = if addr.is_empty() { DEFAULT_SERVE_ADDR.to_string() } else { addr };
+            
+            if choice == "1" {
+                tasks::spawn(&format!("WebSocket server on {}", addr), wsserver::serve(addr.clone(), local));
+            } else {
+                tasks::spawn(&format!("SSE server on {}", addr), sseserver::serve(addr.clone(), local));
+            }
+        },
+        "3" => {
+            tasks::spawn(&format!("Warm-up of {}", settings::model_for(local)), warmup::warmup_model(local));
+        },
+        "4" => {
+            println!("{} Stopped {} background task(s)", status::ok(), tasks::shutdown());
+        },
+        "5" => {},
+        _ => println!("{} Invalid option. Please choose 1-5.", status::err()),
+    }
+}
+
+// Function to show what would be sent for a prompt, for --dry-run
+fn dry_run(prompt: String, local: bool) -> Result<(), Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+    
+    let (_, url) = client::build_client(local)?;
+    println!("Server: {}", url);
+    let model = settings::model_for(local);
+    println!("Model: {}", model);
+    
+    // Show what --preset expanded to, after flags and per-model defaults are combined
+    if let Some(name) = &settings::get().preset_name {
+        let requested = options::requested(&model);
+        println!("Preset: {}", name);
+        let expanded = [
+            ("temperature", requested.temperature.map(|value| value.to_string())),
+            ("top_p", requested.top_p.map(|value| value.to_string())),
+            ("repeat_penalty", requested.repeat_penalty.map(|value| value.to_string())),
+            ("num_ctx", requested.num_ctx.map(|value| value.to_string())),
+        ];
+        for (option, value) in expanded {
+            if let Some(value) = value {
+                println!("  {}: {}", option, value);
+            }
+        }
+    }
+    println!("--- Prompt ---");
+    println!("{}", prompt::prepare(prompt));
+    Ok(())
+}
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+async fn main() {
+    // Usage errors are bad input too; --help and --version still exit with 0
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(if e.use_stderr() { error::EXIT_BAD_INPUT } else { 0 });
+        }
+    };
     
+    if let Err(e) = start(args).await {
+        eprintln!("{} Error: {}", status::err(), e);
+        std::process::exit(error::exit_code(e.as_ref()));
+    }
+}
+
+// Function to load the configuration, run the command and clean up
+async fn start(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    // Must happen before anything reads the environment
+    if args.no_env {
+        settings::disable_env();
+    }
+    
+    if args.list_formats {
+        imagedescriber::list_formats();
+        return Ok(());
+    }
+    
+    if args.template_list {
+        templates::list_templates().map_err(error::bad_input)?;
+        return Ok(());
+    }
+    
+    if args.list_profiles {
+        profiles::list_profiles().map_err(error::bad_input)?;
+        return Ok(());
+    }
+    
+    settings::load_env_files(&args.env_files).map_err(error::bad_input)?;
+    
+    let profile = match &args.profile {
+        Some(name) => Some(profiles::load_profile(name).map_err(error::bad_input)?),
+        None => None,
+    };
+    
+    for path in args.output.iter().chain(&args.save_response_json).chain(&args.export_models) {
+        export::validate_output(path).map_err(error::bad_input)?;
+    }
+    
+    if let Some(path) = &args.prompt_file {
+        args.prompt = Some(prompt::read_prompt_file(path, args.force).map_err(error::bad_input)?);
+    }
+    
+    // Without any words the interactive menu still starts
+    if !args.words.is_empty() {
+        args.prompt = Some(args.words.join(" "));
+    }
+    
+    let (file_context, context_warnings) = prompt::load_context_files(&args.context_files, args.force).map_err(error::bad_input)?;
+    let model_defaults = options::load_model_defaults().map_err(error::bad_input)?;
+    let preset = args.preset.as_deref().map(options::load_preset).transpose().map_err(error::bad_input)?;
+    
+    // status:: reads the settings, and reading them before settings::init would lock in the
+    // defaults, so warnings found while collecting the settings are printed after init
+    let (policy, policy_warning) = match args.policy {
+        Some(policy) => (Some(policy), None),
+        None => match client::policy_from_env() {
+            Ok(policy) => (policy, None),
+            Err(warning) => (None, Some(warning)),
+        },
+    };
+    
+    // An explicit --image-prompt wins over a template
+    let image_prompt = match (&args.image_prompt, &args.image_template) {
+        (None, Some(name)) => Some(templates::load_template(name).map_err(error::bad_input)?),
+        _ => args.image_prompt.clone(),
+    };
+    
+    settings::init(settings::Settings {
+        metrics_format: args.metrics_format.clone(),
+        wrap: args.wrap,
+        expand_env: args.expand_env,
+        dump_image_payload: args.dump_image_payload.clone(),
+        image_prompt,
+        ascii: args.ascii,
+        verbose: args.verbose,
+        profile,
+        profile_name: args.profile.clone(),
+        // --connect fills in whatever --host, --port and --model leave unset
+        host: args.host.clone().or(args.connect.as_ref().map(|connect| connect.host.clone())),
+        port: args.port.or(args.connect.as_ref().and_then(|connect| connect.port)),
+        model: args.model.clone().or(args.connect.as_ref().map(|connect| connect.model.clone())),
+        follow_symlinks: args.follow_symlinks,
+        stream_json: args.stream_json,
+        detect_loops: args.detect_loops,
+        abort_on_loop: args.abort_on_loop,
+        headers: args.headers.clone(),
+        number_code: args.number_code,
+        env_files: args.env_files.clone(),
+        clipboard: args.clipboard,
+        quiet: args.quiet,
+        check_context: args.check_context,
+        num_ctx: args.num_ctx,
+        retry_on_empty: args.retry_on_empty,
+        file_context,
+        echo_prompt: args.echo_prompt,
+        policy,
+        temperature: args.temperature,
+        system: args.system.clone(),
+        model_defaults,
+        https: args.https,
+        retries: args.retries,
+        prompt_prefix: args.prompt_prefix.clone(),
+        prompt_suffix: args.prompt_suffix.clone(),
+        lang: args.lang.clone(),
+        output: args.output.clone(),
+        format_response: args.format_response.unwrap_or_default(),
+        keep_alive: args.keep_alive.clone(),
+        strip_ansi: args.strip_ansi,
+        buffered: args.buffered,
+        force: args.force,
+        save_fetched: args.save_fetched,
+        inline_directives: args.inline_directives,
+        save_response_json: args.save_response_json.clone(),
+        pager: args.pager,
+        wait_for_server: args.wait_for_server,
+        show_params: args.show_params,
+        post_hook: args.post_hook.clone(),
+        post_hook_file: args.post_hook_file.clone(),
+        timing_bar: args.timing_bar,
+        stop_regex: args.stop_regex.clone(),
+        preset,
+        preset_name: args.preset.clone(),
+        live_metrics: args.live_metrics,
+        session: args.session.clone(),
+        max_image_bytes: args.max_image_bytes,
+        self_critique: args.self_critique,
+        max_gen_time: args.max_gen_time,
+        unload_on_exit: args.unload_on_exit,
+    });
+    
+    for warning in policy_warning.into_iter().chain(context_warnings) {
+        eprintln!("{} {}", status::warn(), warning);
+    }
+    
+    let local = args.local || client::prefer_local();
+    
+    // Exit-code driven for Docker HEALTHCHECK and systemd: no output unless it fails
+    if args.healthcheck {
+        if let Err(e) = client::healthcheck(local, Duration::from_secs(args.timeout)).await {
+            eprintln!("unhealthy: {}", e.to_string().lines().next().unwrap_or_default());
+            std::process::exit(error::exit_code(e.as_ref()));
+        }
+        return Ok(());
+    }
+    
+    // Ctrl-C skips the cleanup below, so it unloads the models itself
+    if args.unload_on_exit {
+        tasks::exit_on_ctrl_c();
+    }
+    
+    let result = run(args).await;
+    
+    // Cleanup: free the used models' memory on shared machines
+    warmup::unload_used_models().await;
+    
+    result
+}
+
+// Function to run the requested command or the interactive menu
+async fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
     // Handle command line arguments
     if args.test {
-        connecttoollama::test_connection().await?;
+        if args.local {
+            connectlocally::test_connection().await?;
+        } else {
+            connecttoollama::test_connection().await?;
+        }
         return Ok(());
     }
     
     if let Some(image_file) = args.image {
-        imagedescriber::analyze_specific_image(image_file).await?;
+        let metrics = imagedescriber::analyze_specific_image(image_file, args.prompt).await?;
+        export::deliver(&metrics)?;
+        return Ok(());
+    }
+    
+    if args.image_clipboard {
+        let metrics = imagedescriber::analyze_clipboard_image(args.prompt).await?;
+        export::deliver(&metrics)?;
+        return Ok(());
+    }
+    
+    if args.all_images {
+        imagedescriber::analyze_all_images(args.image_concurrency).await?;
+        return Ok(());
+    }
+    
+    if args.warmup {
+        warmup::warmup_model(args.local).await?;
+        return Ok(());
+    }
+    
+    if args.dry_run {
+        let prompt = match args.prompt {
+            Some(prompt) => prompt,
+            None => connecttoollama::get_user_input("Enter your prompt: ").ok_or(error::ClientError::InputClosed)?,
+        };
+        dry_run(prompt, args.local)?;
+        return Ok(());
+    }
+    
+    if let Some(output) = args.diagnose {
+        diagnose::diagnose(output).await?;
+        return Ok(());
+    }
+    
+    if let Some(tag) = args.sessions_by_tag {
+        sessions::list_by_tag(&tag)?;
+        return Ok(());
+    }
+    
+    if let Some(path) = args.export_models {
+        inventory::export_models(&path, args.local).await?;
+        return Ok(());
+    }
+    
+    if let Some(model) = args.pull {
+        modelmanager::pull(model, args.local).await?;
+        return Ok(());
+    }
+    
+    if args.count_tokens {
+        let prompt = match args.prompt {
+            Some(prompt) => prompt,
+            None => connecttoollama::get_user_input("Enter your prompt: ").ok_or(error::ClientError::InputClosed)?,
+        };
+        tokencount::count_tokens(prompt, args.local, args.json).await?;
+        return Ok(());
+    }
+    
+    if let Some(addr) = args.serve_ws {
+        wsserver::serve(addr, args.local).await?;
+        return Ok(());
+    }
+    
+    if let Some(dir) = args.watch_queue {
+        watchqueue::watch(dir, args.local).await?;
+        return Ok(());
+    }
+    
+    if let Some(addr) = args.sse {
+        sseserver::serve(addr, args.local).await?;
+        return Ok(());
+    }
+    
+    if args.compare_local_remote {
+        let prompt = match args.prompt {
+            Some(prompt) => prompt,
+            None => connecttoollama::get_user_input("Enter your prompt: ").ok_or(error::ClientError::InputClosed)?,
+        };
+        if let Some(metrics) = compare::compare_local_remote(prompt).await? {
+            export::deliver(&metrics)?;
+        }
+        return Ok(());
+    }
+    
+    if args.openai_compat {
+        let prompt = match args.prompt {
+            Some(prompt) => prompt,
+            None => connecttoollama::get_user_input("Enter your prompt: ").ok_or(error::ClientError::InputClosed)?,
+        };
+        openai::chat(prompt, args.local).await?;
+        return Ok(());
+    }
+    
+    if let Some(path) = args.summarize {
+        let metrics = summarize::summarize_file(path, args.local).await?;
+        export::deliver(&metrics)?;
+        return Ok(());
+    }
+    
+    if let Some(count) = args.candidates {
+        let prompt = match args.prompt {
+            Some(prompt) => prompt,
+            None => connecttoollama::get_user_input("Enter your prompt: ").ok_or(error::ClientError::InputClosed)?,
+        };
+        let metrics = candidates::generate_candidates(prompt, count, args.local).await?;
+        export::deliver(&metrics)?;
+        return Ok(());
+    }
+    
+    if args.repl {
+        if let Some(metrics) = repl::run_repl(args.local).await? {
+            export::deliver(&metrics)?;
+        }
+        return Ok(());
+    }
+    
+    if args.tools {
+        let prompt = match args.prompt {
+            Some(prompt) => prompt,
+            None => connecttoollama::get_user_input("Enter your prompt: ").ok_or(error::ClientError::InputClosed)?,
+        };
+        tools::run_tool_demo(prompt, args.local).await?;
         return Ok(());
     }
     
     if args.local {
-        if let Some(prompt) = args.prompt {
-            connectlocally::generate_with_prompt(prompt).await?;
+        let metrics = if let Some(prompt) = args.prompt {
+            connectlocally::generate_with_prompt(prompt).await?
         } else {
-            connectlocally::generate_response().await?;
-        }
+            connectlocally::generate_response().await?
+        };
+        export::deliver(&metrics)?;
         return Ok(());
     }
     
     if let Some(prompt) = args.prompt {
-        connecttoollama::generate_with_prompt(prompt).await?;
+        let metrics = connecttoollama::generate_with_prompt(prompt).await?;
+        export::deliver(&metrics)?;
         return Ok(());
     }
     
-    // Interactive menu
+    // Interactive menu; Ctrl-C also stops anything started under Background Tasks
+    tasks::exit_on_ctrl_c();
     loop {
         display_menu();
         
-        match get_user_choice().as_str() {
+        // Leave quietly when input runs out, e.g. a piped script has ended
+        let Some(choice) = get_user_choice() else {
+            println!();
+            println!("{} Goodbye!", status::bye());
+            break;
+        };
+        
+        match choice.as_str() {
             "1" => {
-                match connecttoollama::generate_response().await {
-                    Ok(_) => println!("✅ Generation completed successfully!"),
-                    Err(e) => println!("❌ Error: {}", e),
+                match connecttoollama::generate_response().await.and_then(|metrics| export::deliver(&metrics)) {
+                    Ok(_) => println!("{} Generation completed successfully!", status::ok()),
+                    Err(e) => println!("{} Error: {}", status::err(), e),
                 }
             },
             "2" => {
-                match connectlocally::generate_response().await {
-                    Ok(_) => println!("✅ Generation completed successfully!"),
-                    Err(e) => println!("❌ Error: {}", e),
+                match connectlocally::generate_response().await.and_then(|metrics| export::deliver(&metrics)) {
+                    Ok(_) => println!("{} Generation completed successfully!", status::ok()),
+                    Err(e) => println!("{} Error: {}", status::err(), e),
                 }
             },
             "3" => {
                 match connecttoollama::test_connection().await {
                     Ok(_) => {},
-                    Err(e) => println!("❌ Error: {}", e),
+                    Err(e) => println!("{} Error: {}", status::err(), e),
                 }
             },
             "4" => {
                 match connectlocally::test_connection().await {
                     Ok(_) => {},
-                    Err(e) => println!("❌ Error: {}", e),
+                    Err(e) => println!("{} Error: {}", status::err(), e),
                 }
             },
             "5" => {
                 display_config();
             },
             "6" => {
-                match imagedescriber::analyze_image().await {
-                    Ok(_) => println!("✅ Image analysis completed successfully!"),
-                    Err(e) => println!("❌ Error: {}", e),
+                match imagedescriber::analyze_image().await.and_then(|metrics| metrics.map_or(Ok(()), |metrics| export::deliver(&metrics))) {
+                    Ok(_) => println!("{} Image analysis completed successfully!", status::ok()),
+                    Err(e) => println!("{} Error: {}", status::err(), e),
                 }
             },
             "7" => {
-                println!("👋 Goodbye!");
+                let Some(prompt) = connecttoollama::get_user_input("Enter your prompt: ") else {
+                    break;
+                };
+                match tools::run_tool_demo(prompt, false).await {
+                    Ok(_) => println!("{} Tool demo completed successfully!", status::ok()),
+                    Err(e) => println!("{} Error: {}", status::err(), e),
+                }
+            },
+            "8" => {
+                match warmup::warmup_model(client::prefer_local()).await {
+                    Ok(_) => {},
+                    Err(e) => println!("{} Error: {}", status::err(), e),
+                }
+            },
+            "9" => {
+                match repl::run_repl(client::prefer_local()).await.and_then(|metrics| metrics.map_or(Ok(()), |metrics| export::deliver(&metrics))) {
+                    Ok(_) => {},
+                    Err(e) => println!("{} Error: {}", status::err(), e),
+                }
+            },
+            "10" => {
+                background_tasks_menu();
+            },
+            "11" => {
+                println!("{} Goodbye!", status::bye());
                 break;
             },
             _ => {
-                println!("❌ Invalid option. Please choose 1-7.");
+                println!("{} Invalid option. Please choose 1-11.", status::err());
             }
         }
         
-        // Add a pause before showing menu again
-        println!("\nPress Enter to continue...");
-        let mut _input = String::new();
-        io::stdin().read_line(&mut _input).unwrap();
+        // Pause before showing the menu again. Piped input (a heredoc or script) has no one
+        // to wait for, and the pause would swallow the next queued line, so it is skipped.
+        // The message goes to stderr to keep stdout to the results.
+        if io::stdin().is_terminal() {
+            eprintln!("\nPress Enter to continue...");
+            if get_user_choice().is_none() {
+                break;
+            }
+        }
+    }
+    
+    // Servers started from the menu don't outlive it
+    let stopped = tasks::shutdown();
+    if stopped > 0 {
+        println!("Stopped {} background task(s)", stopped);
     }
     
     Ok(())
@@ -0,0 +1,374 @@
+use ollama_rs::generation::completion::{GenerationContext, GenerationResponse};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::settings;
+
+// Function to convert an Ollama duration (nanoseconds) to seconds. A u64 holds ~584 years
+// of nanoseconds, so multi-minute generations can't overflow, and f64 stays exact to the
+// nanosecond for anything under ~104 days.
+pub fn nanos_to_secs(nanos: u64) -> f64 {
+    Duration::from_nanos(nanos).as_secs_f64()
+}
+
+// Width of the --timing-bar in characters
+const TIMING_BAR_WIDTH: usize = 40;
+
+// Performance numbers gathered from a single generation.
+// All counters and durations are u64, matching what Ollama sends; missing values are 0.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Metrics {
+    pub request_id: String,
+    pub timestamp: String,
+    pub model: String,
+    pub image: Option<String>,
+    pub connection: Option<String>,
+    pub total_time: f64,
+    // Seconds from sending the request to the first response text (0 if none arrived)
+    pub first_token_time: f64,
+    pub tokens: u64,
+    pub tps: f64,
+    pub eval_count: u64,
+    pub eval_duration: u64,
+    pub total_duration: u64,
+    pub load_duration: u64,
+    pub prompt_eval_count: u64,
+    pub prompt_eval_duration: u64,
+    // Set when the client cut the stream short, e.g. on a detected loop
+    pub stop_reason: Option<String>,
+    // Why the server ended the response, e.g. "stop" or "length" (when it sent one)
+    pub done_reason: Option<String>,
+    // True when tokens/tps come from a word count because the server sent no eval_count
+    pub estimated: bool,
+    // The full response text and the conversation context returned on the final chunk
+    #[serde(skip)]
+    pub response: String,
+    #[serde(skip)]
+    pub context: Option<GenerationContext>,
+    // The final (done) chunk as the server sent it, for --save-response-json
+    #[serde(skip)]
+    pub final_response: Option<GenerationResponse>,
+}
+
+impl Metrics {
+    // Function to start collecting metrics for a generation with the given model
+    pub fn new(model: &str) -> Self {
+        Metrics {
+            model: model.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // Function to copy the counters Ollama sends on the final (done) chunk
+    pub fn record_final(&mut self, resp: &GenerationResponse) {
+        self.eval_count = resp.eval_count.unwrap_or(0);
+        self.eval_duration = resp.eval_duration.unwrap_or(0);
+        self.total_duration = resp.total_duration.unwrap_or(0);
+        self.load_duration = resp.load_duration.unwrap_or(0);
+        self.prompt_eval_count = resp.prompt_eval_count.unwrap_or(0);
+        self.prompt_eval_duration = resp.prompt_eval_duration.unwrap_or(0);
+        self.context = resp.context.clone();
+        self.final_response = Some(resp.clone());
+    }
+
+    // Function to compute the client-side numbers once the stream has finished
+    pub fn finish(&mut self, elapsed: Duration, response_text: &str) {
+        self.response = response_text.to_string();
+
+        // Use eval_count if available, otherwise estimate from text
+        self.estimated = self.eval_count == 0 && !response_text.trim().is_empty();
+        self.tokens = if self.eval_count > 0 {
+            self.eval_count
+        } else {
+            response_text.split_whitespace().count() as u64
+        };
+
+        self.total_time = elapsed.as_secs_f64();
+        self.tps = if self.total_time > 0.0 {
+            self.tokens as f64 / self.total_time
+        } else {
+            0.0
+        };
+    }
+
+    pub fn eval_time(&self) -> f64 {
+        nanos_to_secs(self.eval_duration)
+    }
+
+    pub fn ollama_total_time(&self) -> f64 {
+        nanos_to_secs(self.total_duration)
+    }
+
+    pub fn load_time(&self) -> f64 {
+        nanos_to_secs(self.load_duration)
+    }
+
+    pub fn prompt_eval_time(&self) -> f64 {
+        nanos_to_secs(self.prompt_eval_duration)
+    }
+
+    pub fn prompt_tps(&self) -> f64 {
+        let prompt_eval_time = self.prompt_eval_time();
+        if prompt_eval_time > 0.0 {
+            self.prompt_eval_count as f64 / prompt_eval_time
+        } else {
+            0.0
+        }
+    }
+
+    pub fn ollama_tps(&self) -> f64 {
+        let eval_time = self.eval_time();
+        if eval_time > 0.0 {
+            self.eval_count as f64 / eval_time
+        } else {
+            0.0
+        }
+    }
+
+    // Function to print the metrics, either as the default block or through a user template
+    pub fn print(&self, format: Option<&str>) {
+        // In --stream-json mode the metrics were already sent as the final JSON object
+        if settings::get().stream_json {
+            return;
+        }
+
+        if let Some(template) = format {
+            println!("\n{}", render_template(template, self));
+            self.print_timing_bar();
+            return;
+        }
+
+        println!("\n--- Performance Metrics ---");
+        if let Some(image) = &self.image {
+            println!("Image: {}", image);
+        }
+        if let Some(connection) = &self.connection {
+            println!("Connection: {}", connection);
+        }
+        println!("Total time: {:.2}s", self.total_time);
+        if self.first_token_time > 0.0 {
+            println!("Time to first token: {:.2}s", self.first_token_time);
+        }
+        println!("Tokens generated: {}", self.tokens);
+        println!("Tokens per second: {:.2}", self.tps);
+        if self.estimated {
+            if self.stop_reason.is_some() {
+                println!("(metrics estimated; the stream stopped before the server reported eval counts)");
+            } else {
+                println!("(metrics estimated; server did not report eval counts)");
+            }
+        }
+
+        if let Some(reason) = &self.stop_reason {
+            println!("Stopped early: {}", reason);
+        }
+
+        // If we have detailed timing from Ollama
+        if self.load_duration > 0 {
+            println!("Model load time: {:.2}s", self.load_time());
+        }
+
+        if self.prompt_eval_duration > 0 {
+            println!("Prompt tokens: {}", self.prompt_eval_count);
+            println!("Prompt eval time: {:.2}s", self.prompt_eval_time());
+            println!("Prompt tokens/sec: {:.2}", self.prompt_tps());
+        }
+
+        if self.eval_duration > 0 {
+            println!("Ollama eval time: {:.2}s", self.eval_time());
+            println!("Ollama tokens/sec: {:.2}", self.ollama_tps());
+        }
+
+        if self.total_duration > 0 {
+            println!("Ollama total time: {:.2}s", self.ollama_total_time());
+        }
+
+        self.print_timing_bar();
+        println!("----------------------------");
+    }
+
+    // Function to print the timing bar when --timing-bar is set
+    pub fn print_timing_bar(&self) {
+        if settings::get().timing_bar && !settings::get().stream_json {
+            if let Some(bar) = self.timing_bar() {
+                println!("{}", bar);
+            }
+        }
+    }
+
+    // Function to draw where the server's time went as a stacked bar (--timing-bar), e.g.
+    // [LLLLLLLLLLLLLLLLLLLLLLLLLLLLLLLLPPPGGGGG] load 80% | prompt 8% | generate 12%
+    // Time Ollama reports but doesn't break down is shown as "other". Returns None without timings.
+    pub fn timing_bar(&self) -> Option<String> {
+        let parts = [self.load_duration, self.prompt_eval_duration, self.eval_duration];
+        let measured: u64 = parts.iter().sum();
+        // total_duration can be missing or, with rounding, a little below the sum of its parts
+        let total = self.total_duration.max(measured);
+        if total == 0 {
+            return None;
+        }
+
+        let segments = [
+            ('L', "load", parts[0]),
+            ('P', "prompt", parts[1]),
+            ('G', "generate", parts[2]),
+            ('.', "other", total - measured),
+        ];
+
+        // Place each boundary by the running total so the widths always add up to the bar width
+        let mut bar = String::new();
+        let mut labels = Vec::new();
+        let mut elapsed = 0u64;
+        for (symbol, name, duration) in segments {
+            let start = (elapsed as f64 / total as f64 * TIMING_BAR_WIDTH as f64).round() as usize;
+            elapsed += duration;
+            let end = (elapsed as f64 / total as f64 * TIMING_BAR_WIDTH as f64).round() as usize;
+            bar.extend(std::iter::repeat_n(symbol, end - start));
+
+            if duration > 0 {
+                labels.push(format!("{} {:.0}%", name, duration as f64 / total as f64 * 100.0));
+            }
+        }
+
+        Some(format!("Timing: [{}] {}", bar, labels.join(" | ")))
+    }
+
+    // Numeric fields available to templates; text fields are handled separately
+    fn numeric_field(&self, name: &str) -> Option<f64> {
+        match name {
+            "total_time" => Some(self.total_time),
+            "first_token_time" => Some(self.first_token_time),
+            "tokens" => Some(self.tokens as f64),
+            "tps" => Some(self.tps),
+            "eval_count" => Some(self.eval_count as f64),
+            "eval_time" => Some(self.eval_time()),
+            "ollama_tps" => Some(self.ollama_tps()),
+            "ollama_total_time" => Some(self.ollama_total_time()),
+            "load_time" => Some(self.load_time()),
+            "prompt_eval_count" => Some(self.prompt_eval_count as f64),
+            "prompt_eval_time" => Some(self.prompt_eval_time()),
+            "prompt_tps" => Some(self.prompt_tps()),
+            _ => None,
+        }
+    }
+
+    fn text_field(&self, name: &str) -> Option<String> {
+        match name {
+            "request_id" => Some(self.request_id.clone()),
+            "timestamp" => Some(self.timestamp.clone()),
+            "model" => Some(self.model.clone()),
+            "image" => Some(self.image.clone().unwrap_or_default()),
+            "connection" => Some(self.connection.clone().unwrap_or_default()),
+            "stop_reason" => Some(self.stop_reason.clone().unwrap_or_default()),
+            "estimated" => Some(self.estimated.to_string()),
+            _ => None,
+        }
+    }
+}
+
+// Function to fill a template like "{model}: {tps:.1} tok/s in {total_time:.2}s" with metric values.
+// Unknown placeholders are left untouched so typos are easy to spot in the output.
+pub fn render_template(template: &str, metrics: &Metrics) -> String {
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        output.push_str(&rest[..open]);
+        let after_open = &rest[open + 1..];
+
+        let Some(close) = after_open.find('}') else {
+            output.push_str(&rest[open..]);
+            return output;
+        };
+
+        let placeholder = &after_open[..close];
+        let (name, spec) = match placeholder.split_once(':') {
+            Some((name, spec)) => (name, Some(spec)),
+            None => (placeholder, None),
+        };
+
+        // Only ".N" precision specs are supported
+        let precision = spec
+            .and_then(|spec| spec.strip_prefix('.'))
+            .and_then(|digits| digits.parse::<usize>().ok());
+
+        if let Some(value) = metrics.numeric_field(name) {
+            match precision {
+                Some(precision) => output.push_str(&format!("{:.*}", precision, value)),
+                None if matches!(name, "tokens" | "eval_count" | "prompt_eval_count") => output.push_str(&format!("{}", value as u64)),
+                None => output.push_str(&format!("{:.2}", value)),
+            }
+        } else if let Some(text) = metrics.text_field(name) {
+            output.push_str(&text);
+        } else {
+            output.push_str(&rest[open..open + close + 2]);
+        }
+
+        rest = &after_open[close + 1..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nanos_to_secs_handles_multi_minute_generations() {
+        assert_eq!(nanos_to_secs(300_000_000_000), 300.0);
+        assert_eq!(nanos_to_secs(754_500_000_000), 754.5);
+    }
+
+    #[test]
+    fn nanos_to_secs_handles_the_largest_durations() {
+        assert!((nanos_to_secs(u64::MAX) - 18_446_744_073.709_553).abs() < 1e-3);
+        assert!((nanos_to_secs(u64::MAX - 1) - nanos_to_secs(u64::MAX)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn timing_bar_fills_the_width_when_total_is_below_the_parts() {
+        let metrics = Metrics {
+            load_duration: 30,
+            prompt_eval_duration: 10,
+            eval_duration: 60,
+            total_duration: 90,
+            ..Default::default()
+        };
+
+        let expected = format!("Timing: [{}{}{}] load 30% | prompt 10% | generate 60%", "L".repeat(12), "P".repeat(4), "G".repeat(24));
+        assert_eq!(metrics.timing_bar(), Some(expected));
+    }
+
+    #[test]
+    fn timing_bar_needs_timings() {
+        assert_eq!(Metrics::default().timing_bar(), None);
+    }
+
+    fn template_metrics() -> Metrics {
+        Metrics { model: "llama3.2".to_string(), tps: 38.456, total_time: 2.5, tokens: 96, eval_count: 96, ..Default::default() }
+    }
+
+    #[test]
+    fn render_template_applies_precision() {
+        assert_eq!(render_template("{model}: {tps:.1} tok/s in {total_time:.2}s", &template_metrics()), "llama3.2: 38.5 tok/s in 2.50s");
+        assert_eq!(render_template("{tps:.0} / {tps}", &template_metrics()), "38 / 38.46");
+    }
+
+    #[test]
+    fn render_template_prints_counts_as_integers() {
+        assert_eq!(render_template("{tokens} tokens, {eval_count} evaluated, {prompt_eval_count} prompt", &template_metrics()), "96 tokens, 96 evaluated, 0 prompt");
+    }
+
+    #[test]
+    fn render_template_keeps_unknown_placeholders() {
+        assert_eq!(render_template("{tsp:.1} tok/s for {modle}", &template_metrics()), "{tsp:.1} tok/s for {modle}");
+    }
+
+    #[test]
+    fn render_template_keeps_an_unclosed_brace() {
+        assert_eq!(render_template("{model} {tps", &template_metrics()), "llama3.2 {tps");
+        assert_eq!(render_template("{", &template_metrics()), "{");
+    }
+}
@@ -0,0 +1,234 @@
+use ollama_rs::{Ollama, error::OllamaError, models::LocalModel};
+use ollama_rs::generation::completion::request::GenerationRequest;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::ClientError;
+use crate::streaming::{self, ChunkStream, DoneReason};
+use crate::{client, lineeditor, logging, settings, status};
+
+// How long a fetched model list is reused before asking the server again.
+// Override with `model_cache_ttl=<seconds>` in .env (0 disables caching).
+const DEFAULT_CACHE_TTL_SECS: u64 = 30;
+
+struct CachedModels {
+    host: String,
+    fetched_at: Instant,
+    models: Vec<LocalModel>,
+}
+
+static MODEL_CACHE: Mutex<Option<CachedModels>> = Mutex::new(None);
+
+// Function to read the cache TTL from .env, falling back to the default
+fn cache_ttl() -> Duration {
+    settings::load_dotenv();
+
+    let secs = settings::env_var("model_cache_ttl")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_CACHE_TTL_SECS);
+    Duration::from_secs(secs)
+}
+
+// Function to list the models on a server, reusing a recent result for the same host
+pub async fn list_models_cached(ollama: &Ollama, host: &str) -> Result<Vec<LocalModel>, OllamaError> {
+    let ttl = cache_ttl();
+
+    if let Some(cached) = MODEL_CACHE.lock().unwrap().as_ref() {
+        if cached.host == host && cached.fetched_at.elapsed() < ttl {
+            return Ok(cached.models.clone());
+        }
+    }
+
+    let models = ollama.list_local_models().await?;
+
+    *MODEL_CACHE.lock().unwrap() = Some(CachedModels {
+        host: host.to_string(),
+        fetched_at: Instant::now(),
+        models: models.clone(),
+    });
+
+    Ok(models)
+}
+
+// Function to drop the cached model list; call after pulling, deleting or creating a model
+pub fn invalidate_cache() {
+    *MODEL_CACHE.lock().unwrap() = None;
+}
+
+// Function to split a model name into its name and tag. Registry-qualified names such as
+// hf.co/user/model:Q4_K_M or localhost:5000/team/model are kept whole: only a colon in the
+// last path segment starts the tag, since a registry host can carry a port.
+pub fn split_tag(name: &str) -> (&str, Option<&str>) {
+    let segment_start = name.rfind('/').map(|i| i + 1).unwrap_or(0);
+    match name[segment_start..].rfind(':') {
+        Some(i) => (&name[..segment_start + i], Some(&name[segment_start + i + 1..])),
+        None => (name, None),
+    }
+}
+
+// Function to add the implicit :latest tag the server uses, so "llama3.2" becomes "llama3.2:latest"
+pub fn normalize_model_name(name: &str) -> String {
+    match split_tag(name) {
+        (_, Some(_)) => name.to_string(),
+        (base, None) => format!("{}:latest", base),
+    }
+}
+
+// Function to compare model names the way the server does; "llava" matches "llava:latest"
+pub fn same_model(a: &str, b: &str) -> bool {
+    normalize_model_name(a) == normalize_model_name(b)
+}
+
+// Function to tell whether a request failed because the model isn't installed.
+// The server answers with e.g. `model "llama3.2" not found, try pulling it first`.
+pub fn is_missing_model(error: &dyn std::error::Error) -> bool {
+    let message = error.to_string();
+    message.contains("model") && message.contains("not found")
+}
+
+// Function to check a model against a server's model list, returning the error to report when
+// it is missing. A fresh install with nothing at all gets the onboarding message rather than
+// an error that blames the one model.
+pub fn check_installed(installed: &[LocalModel], model: &str) -> Result<(), ClientError> {
+    if installed.iter().any(|m| same_model(&m.name, model)) {
+        Ok(())
+    } else if installed.is_empty() {
+        Err(ClientError::NoModelsInstalled { model: model.to_string() })
+    } else {
+        Err(ClientError::ModelNotFound { model: model.to_string() })
+    }
+}
+
+// Function to download a model and drop the stale model list
+pub async fn pull_model(ollama: &Ollama, model: &str) -> Result<(), Box<dyn std::error::Error>> {
+    println!("Pulling model {} (this can take a while)...", model);
+    ollama.pull_model(model.to_string(), false).await?;
+    invalidate_cache();
+    println!("{} Model {} is installed", status::ok(), model);
+    Ok(())
+}
+
+// Function to handle --pull: download the configured or given model
+pub async fn pull(model: String, local: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (ollama, url) = client::build_client(local)?;
+    println!("Server: {}", url);
+    pull_model(&ollama, &model).await
+}
+
+// Function to start a generation stream, offering to pull the model when it is missing.
+// Only interactive callers are asked; scripts get a hint instead of a prompt.
+pub async fn generate_stream_or_pull(ollama: &Ollama, local: bool, request: &GenerationRequest<'_>, interactive: bool) -> Result<(ChunkStream, DoneReason), Box<dyn std::error::Error>> {
+    // The error isn't Send, so it isn't kept across the awaits below
+    match streaming::open_generate_stream(local, request).await {
+        Ok(opened) => return Ok(opened),
+        Err(e) if is_missing_model(e.as_ref()) => logging::debug(&format!("missing model: {}", e)),
+        Err(e) => return Err(e),
+    }
+
+    let model = request.model_name.clone();
+
+    // The model list tells a fresh install apart; without it, blame the model
+    let missing = ollama.list_local_models().await.ok()
+        .and_then(|models| check_installed(&models, &model).err())
+        .unwrap_or_else(|| ClientError::ModelNotFound { model: model.clone() });
+
+    if !interactive {
+        return Err(missing.into());
+    }
+
+    offer_pull(ollama, &model, &missing).await?;
+    streaming::open_generate_stream(local, request).await
+}
+
+// Function to ask whether to pull a missing model, and pull it if the answer is yes
+async fn offer_pull(ollama: &Ollama, model: &str, missing: &ClientError) -> Result<(), Box<dyn std::error::Error>> {
+    if matches!(missing, ClientError::NoModelsInstalled { .. }) {
+        println!("{} No models are installed on this server yet.", status::warn());
+    }
+
+    let answer = lineeditor::read_line(&format!("Model '{}' is not installed. Pull it now? (y/N) ", model))
+        .unwrap_or_default();
+    if !matches!(answer.to_lowercase().as_str(), "y" | "yes") {
+        return Err(ClientError::ModelNotFound { model: model.to_string() }.into());
+    }
+
+    pull_model(ollama, model).await
+}
+
+// Function to offer pulling a missing model before a request starts, for callers that can't
+// stop to ask once it is running (the REPL reads the terminal for /cancel while streaming).
+// A server that can't list its models is left for the request itself to report.
+pub async fn ensure_installed(ollama: &Ollama, host: &str, model: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let Ok(models) = list_models_cached(ollama, host).await else {
+        return Ok(());
+    };
+    let Err(missing) = check_installed(&models, model) else {
+        return Ok(());
+    };
+
+    offer_pull(ollama, model, &missing).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_model_adds_the_latest_tag() {
+        assert!(same_model("llama3.2", "llama3.2:latest"));
+        assert!(same_model("llama3.2:latest", "llama3.2"));
+        assert!(same_model("llama3.2", "llama3.2"));
+    }
+
+    #[test]
+    fn same_model_tells_tags_apart() {
+        assert!(!same_model("llama3.2", "llama3.2:1b"));
+        assert!(!same_model("llama3.2:1b", "llama3.2:3b"));
+        assert!(!same_model("llama3.2", "llama3.1"));
+    }
+
+    #[test]
+    fn split_tag_handles_plain_names() {
+        assert_eq!(split_tag("llama3.2"), ("llama3.2", None));
+        assert_eq!(split_tag("llama3.2:1b"), ("llama3.2", Some("1b")));
+    }
+
+    #[test]
+    fn split_tag_ignores_a_registry_port() {
+        assert_eq!(split_tag("host:5000/ns/model:tag"), ("host:5000/ns/model", Some("tag")));
+        assert_eq!(split_tag("host:5000/ns/model"), ("host:5000/ns/model", None));
+        assert_eq!(split_tag("localhost:5000/team/model:v2"), ("localhost:5000/team/model", Some("v2")));
+    }
+
+    #[test]
+    fn split_tag_handles_registry_names() {
+        assert_eq!(split_tag("hf.co/user/model:Q4_K_M"), ("hf.co/user/model", Some("Q4_K_M")));
+        assert_eq!(normalize_model_name("host:5000/ns/model"), "host:5000/ns/model:latest");
+        assert!(same_model("host:5000/ns/model", "host:5000/ns/model:latest"));
+    }
+
+    fn models(names: &[&str]) -> Vec<LocalModel> {
+        names.iter()
+            .map(|name| serde_json::from_value(serde_json::json!({ "name": name, "modified_at": "2024-01-01T00:00:00Z", "size": 1 })).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn check_installed_finds_the_model() {
+        assert!(check_installed(&models(&["llava:latest", "llama3.2:latest"]), "llama3.2").is_ok());
+    }
+
+    #[test]
+    fn check_installed_reports_an_empty_model_list() {
+        let error = check_installed(&[], "llama3.2").unwrap_err();
+        assert!(matches!(&error, ClientError::NoModelsInstalled { model } if model == "llama3.2"));
+        assert_eq!(error.to_string(), "No models are installed. Pull one with --pull llama3.2 to get started.");
+    }
+
+    #[test]
+    fn check_installed_reports_a_missing_model() {
+        let error = check_installed(&models(&["llava:latest"]), "llama3.2:1b").unwrap_err();
+        assert!(matches!(&error, ClientError::ModelNotFound { model } if model == "llama3.2:1b"));
+    }
+}
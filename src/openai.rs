@@ -0,0 +1,77 @@
+use serde_json::{json, Value};
+use std::io::{stdout, Write};
+use std::time::Instant;
+
+use crate::metrics::Metrics;
+use crate::{client, error, logging, prompt, settings};
+
+// Function to send a prompt through Ollama's OpenAI-compatible /v1/chat/completions
+// endpoint instead of the native API, streaming the reply as server-sent events.
+pub async fn chat(prompt: String, local: bool) -> Result<(), Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+
+    let prompt = prompt::prepare(prompt);
+    let request_id = logging::start_request(&prompt);
+    let model = settings::model_for(local);
+
+    let (http_client, base_url) = client::build_http_client(local)?;
+    let endpoint = format!("{}/v1/chat/completions", base_url);
+    logging::info(&format!("Connecting to: {} (OpenAI-compatible)", endpoint));
+    logging::info(&format!("Using model: {}", model));
+
+    let body = json!({
+        "model": model,
+        "messages": [{ "role": "user", "content": prompt }],
+        "stream": true,
+        "stream_options": { "include_usage": true },
+    });
+
+    let start_time = Instant::now();
+    let response = http_client.post(&endpoint).json(&body).send().await?;
+    let mut response = error::check_response(response).await?;
+
+    logging::info("\n--- Response ---");
+
+    let mut metrics = Metrics::new(&model);
+    metrics.request_id = request_id;
+    metrics.timestamp = logging::timestamp();
+
+    let mut response_text = String::new();
+    let mut pending = String::new();
+
+    // Each event is a line "data: {...}"; the stream ends with "data: [DONE]"
+    'events: while let Some(bytes) = response.chunk().await? {
+        pending.push_str(&String::from_utf8_lossy(&bytes));
+
+        while let Some(newline) = pending.find('\n') {
+            let line: String = pending.drain(..=newline).collect();
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim();
+            if data == "[DONE]" {
+                break 'events;
+            }
+
+            let event: Value = serde_json::from_str(data)?;
+            if let Some(delta) = event["choices"][0]["delta"]["content"].as_str() {
+                print!("{}", delta);
+                stdout().flush()?;
+                response_text.push_str(delta);
+            }
+            if let Some(usage) = event.get("usage").filter(|usage| !usage.is_null()) {
+                metrics.eval_count = usage["completion_tokens"].as_u64().unwrap_or(0);
+                metrics.prompt_eval_count = usage["prompt_tokens"].as_u64().unwrap_or(0);
+            }
+        }
+    }
+
+    metrics.finish(start_time.elapsed(), &response_text);
+
+    match settings::get().metrics_format.as_deref() {
+        Some(format) => metrics.print(Some(format)),
+        None => logging::info(""),
+    }
+
+    Ok(())
+}
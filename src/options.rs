@@ -0,0 +1,229 @@
+use ollama_rs::generation::completion::request::GenerationRequest;
+use ollama_rs::generation::parameters::{KeepAlive, TimeUnit};
+use ollama_rs::models::ModelOptions;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::{modelmanager, settings};
+
+// Per-model default options live next to .env in model_defaults.toml, e.g.
+//
+// [codellama]
+// temperature = 0.2
+// num_ctx = 8192
+// system = "You are a concise coding assistant."
+const MODEL_DEFAULTS_FILE: &str = "model_defaults.toml";
+
+// Options applied whenever a model is used, unless a flag overrides them
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ModelDefaults {
+    pub temperature: Option<f32>,
+    pub num_ctx: Option<u64>,
+    pub system: Option<String>,
+}
+
+// User presets live next to .env in presets.toml and replace a built-in of the same name, e.g.
+//
+// [brainstorm]
+// temperature = 1.2
+// top_p = 0.98
+const PRESETS_FILE: &str = "presets.toml";
+
+// A named bundle of sampling options for --preset
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Preset {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub repeat_penalty: Option<f32>,
+    pub num_ctx: Option<u64>,
+}
+
+// Built-in presets: name, temperature, top_p, repeat_penalty, num_ctx
+const BUILT_IN_PRESETS: &[(&str, f32, f32, f32, Option<u64>)] = &[
+    ("creative", 1.0, 0.95, 1.1, None),
+    ("precise", 0.2, 0.5, 1.1, None),
+    ("code", 0.1, 0.9, 1.05, Some(8192)),
+];
+
+// Function to look up a preset by name: presets.toml first, then the built-ins
+pub fn load_preset(name: &str) -> Result<Preset, Box<dyn std::error::Error>> {
+    let path = Path::new(PRESETS_FILE);
+    let mut user_presets: BTreeMap<String, Preset> = BTreeMap::new();
+    if path.exists() {
+        let contents = fs::read_to_string(path)?;
+        user_presets = toml::from_str(&contents)
+            .map_err(|e| format!("Could not parse {}: {}", PRESETS_FILE, e))?;
+    }
+
+    if let Some(preset) = user_presets.remove(name) {
+        return Ok(preset);
+    }
+
+    if let Some(&(_, temperature, top_p, repeat_penalty, num_ctx)) = BUILT_IN_PRESETS.iter().find(|(known, _, _, _, _)| *known == name) {
+        return Ok(Preset {
+            temperature: Some(temperature),
+            top_p: Some(top_p),
+            repeat_penalty: Some(repeat_penalty),
+            num_ctx,
+        });
+    }
+
+    let mut available: Vec<String> = BUILT_IN_PRESETS.iter().map(|(known, _, _, _, _)| known.to_string()).collect();
+    available.extend(user_presets.into_keys());
+    Err(format!("Unknown preset '{}'. Available: {}", name, available.join(", ")).into())
+}
+
+// Language names for --lang; other codes are passed to the model verbatim
+const LANGUAGES: &[(&str, &str)] = &[
+    ("ar", "Arabic"), ("de", "German"), ("en", "English"), ("es", "Spanish"),
+    ("fr", "French"), ("hi", "Hindi"), ("it", "Italian"), ("ja", "Japanese"),
+    ("ko", "Korean"), ("nl", "Dutch"), ("pl", "Polish"), ("pt", "Portuguese"),
+    ("ru", "Russian"), ("sv", "Swedish"), ("tr", "Turkish"), ("uk", "Ukrainian"),
+    ("zh", "Chinese"),
+];
+
+// Function to turn a language code like "fr" or "pt-BR" into an instruction for the model
+pub fn language_instruction(code: &str) -> String {
+    let base = code.split(['-', '_']).next().unwrap_or(code).to_lowercase();
+    let name = LANGUAGES
+        .iter()
+        .find(|(known, _)| *known == base)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| code.to_string());
+    format!("Respond in {}.", name)
+}
+
+// Function to read the per-model defaults (an empty map if the file doesn't exist)
+pub fn load_model_defaults() -> Result<BTreeMap<String, ModelDefaults>, Box<dyn std::error::Error>> {
+    let path = Path::new(MODEL_DEFAULTS_FILE);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let contents = fs::read_to_string(path)?;
+    let defaults = toml::from_str(&contents)
+        .map_err(|e| format!("Could not parse {}: {}", MODEL_DEFAULTS_FILE, e))?;
+    Ok(defaults)
+}
+
+// Function to find the defaults for a model; an entry for "codellama" also covers "codellama:13b"
+pub fn defaults_for(model: &str) -> ModelDefaults {
+    let all = &settings::get().model_defaults;
+
+    all.get(model)
+        .or_else(|| all.get(modelmanager::split_tag(model).0))
+        .cloned()
+        .unwrap_or_default()
+}
+
+// Function to parse a --keep-alive value: -1 (keep loaded), 0 (unload right away),
+// or a duration such as 30s, 10m or 2h (a bare number means seconds)
+pub fn parse_keep_alive(raw: &str) -> Result<KeepAlive, String> {
+    let raw = raw.trim();
+    match raw {
+        "-1" => return Ok(KeepAlive::Indefinitely),
+        "0" => return Ok(KeepAlive::UnloadOnCompletion),
+        _ => {},
+    }
+
+    let (number, unit) = match raw.char_indices().last() {
+        Some((i, 's')) => (&raw[..i], TimeUnit::Seconds),
+        Some((i, 'm')) => (&raw[..i], TimeUnit::Minutes),
+        Some((i, 'h')) => (&raw[..i], TimeUnit::Hours),
+        _ => (raw, TimeUnit::Seconds),
+    };
+
+    let time = number
+        .parse::<u64>()
+        .map_err(|_| format!("invalid keep-alive '{}': use -1, 0 or a duration like 30s, 10m, 2h", raw))?;
+    Ok(KeepAlive::Until { time, unit })
+}
+
+// Function to check a --keep-alive value while parsing arguments
+pub fn validate_keep_alive(raw: &str) -> Result<String, String> {
+    parse_keep_alive(raw).map(|_| raw.trim().to_string())
+}
+
+// Function to get the --keep-alive setting (already validated at startup)
+pub fn keep_alive() -> Option<KeepAlive> {
+    settings::get().keep_alive.as_deref().and_then(|raw| parse_keep_alive(raw).ok())
+}
+
+// Function to add the generation options to a request: flags first, then --preset, then the model's defaults
+pub fn apply<'a>(request: GenerationRequest<'a>, model: &str) -> GenerationRequest<'a> {
+    apply_seeded(request, model, None)
+}
+
+// The options a request carries once flags and the model's defaults are combined
+pub struct RequestedOptions {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub repeat_penalty: Option<f32>,
+    pub num_ctx: Option<u64>,
+    pub system: Option<String>,
+}
+
+// Function to combine the flags with the preset and the model's defaults: flags first
+pub fn requested(model: &str) -> RequestedOptions {
+    let settings = settings::get();
+    let defaults = defaults_for(model);
+    let preset = settings.preset.clone().unwrap_or_default();
+
+    let mut system = settings.system.clone().or(defaults.system);
+
+    // --lang adds its instruction after any other system prompt
+    if let Some(lang) = &settings.lang {
+        let instruction = language_instruction(lang);
+        system = Some(match system {
+            Some(system) => format!("{}\n{}", system, instruction),
+            None => instruction,
+        });
+    }
+
+    RequestedOptions {
+        temperature: settings.temperature.or(preset.temperature).or(defaults.temperature),
+        top_p: preset.top_p,
+        repeat_penalty: preset.repeat_penalty,
+        num_ctx: settings.num_ctx.or(preset.num_ctx).or(defaults.num_ctx),
+        system,
+    }
+}
+
+// Function to add the generation options plus a fixed sampling seed (e.g. for --candidates)
+pub fn apply_seeded<'a>(request: GenerationRequest<'a>, model: &str, seed: Option<i32>) -> GenerationRequest<'a> {
+    let RequestedOptions { temperature, top_p, repeat_penalty, num_ctx, system } = requested(model);
+
+    let mut request = request;
+
+    if temperature.is_some() || top_p.is_some() || repeat_penalty.is_some() || num_ctx.is_some() || seed.is_some() {
+        let mut options = ModelOptions::default();
+        if let Some(temperature) = temperature {
+            options = options.temperature(temperature);
+        }
+        if let Some(top_p) = top_p {
+            options = options.top_p(top_p);
+        }
+        if let Some(repeat_penalty) = repeat_penalty {
+            options = options.repeat_penalty(repeat_penalty);
+        }
+        if let Some(num_ctx) = num_ctx {
+            options = options.num_ctx(num_ctx);
+        }
+        if let Some(seed) = seed {
+            options = options.seed(seed);
+        }
+        request = request.options(options);
+    }
+
+    if let Some(system) = system {
+        request = request.system(system);
+    }
+
+    if let Some(keep_alive) = keep_alive() {
+        request = request.keep_alive(keep_alive);
+    }
+
+    request
+}
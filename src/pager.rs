@@ -0,0 +1,40 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::status;
+
+// Used when $PAGER isn't set; -R keeps any colors intact
+const DEFAULT_PAGER: &str = "less -R";
+
+// Function to show text in $PAGER (or less) and wait until the user closes it.
+// Falls back to printing the text when no pager can be started.
+pub fn show(text: &str) {
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().unwrap_or("less");
+
+    let child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            println!("{} Could not start pager '{}': {}", status::warn(), pager, e);
+            print!("{}", text);
+            let _ = std::io::stdout().flush();
+            return;
+        }
+    };
+
+    // The pager closing early (e.g. pressing q) breaks the pipe, which isn't an error
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    let _ = child.wait();
+}
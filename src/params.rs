@@ -0,0 +1,86 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::{client, error, logging, options, settings, status};
+
+// Function to fetch the model's modelfile parameters and trained context length via /api/show.
+// Parameters come one per line, e.g. "temperature    0.7"; repeated keys such as stop are joined.
+async fn model_parameters(local: bool, model: &str) -> Result<(BTreeMap<String, String>, Option<u64>), Box<dyn std::error::Error>> {
+    let (http_client, base_url) = client::build_http_client(local)?;
+    let response = http_client
+        .post(format!("{}/api/show", base_url))
+        .json(&json!({ "model": model }))
+        .send()
+        .await?;
+    let info: Value = error::check_response(response).await?.json().await?;
+
+    let mut parameters: BTreeMap<String, String> = BTreeMap::new();
+    for line in info.get("parameters").and_then(Value::as_str).unwrap_or_default().lines() {
+        if let Some((key, value)) = line.trim().split_once(char::is_whitespace) {
+            let value = value.trim().to_string();
+            parameters.entry(key.to_string())
+                .and_modify(|existing| existing.push_str(&format!(", {}", value)))
+                .or_insert(value);
+        }
+    }
+
+    // model_info keys are prefixed with the architecture, e.g. "llama.context_length"
+    let context_length = info.get("model_info")
+        .and_then(Value::as_object)
+        .and_then(|model_info| model_info.iter().find(|(key, _)| key.ends_with(".context_length")))
+        .and_then(|(_, value)| value.as_u64());
+
+    Ok((parameters, context_length))
+}
+
+// Function to print the parameters the server used for a request (--show-params): each value the
+// client sent next to the model's default, plus the modelfile's other defaults. Ollama doesn't
+// echo options back, so a num_ctx above the model's trained context is reported as clamped.
+pub async fn show_effective(local: bool, model: &str) {
+    if !settings::get().show_params {
+        return;
+    }
+
+    let (mut defaults, context_length) = match model_parameters(local, model).await {
+        Ok(parameters) => parameters,
+        Err(e) => {
+            logging::info(&format!("{} Could not read the parameters of {}: {}", status::warn(), model, e));
+            return;
+        }
+    };
+
+    let requested = options::requested(model);
+    logging::info("--- Parameters ---");
+
+    let mut show = |name: &str, requested: Option<String>| {
+        let default = defaults.remove(name);
+        match (requested, default) {
+            (Some(requested), Some(default)) if requested != default => {
+                logging::info(&format!("{}: {} (requested; model default {})", name, requested, default));
+            },
+            (Some(requested), _) => logging::info(&format!("{}: {} (requested)", name, requested)),
+            (None, Some(default)) => logging::info(&format!("{}: {} (model default)", name, default)),
+            (None, None) => {},
+        }
+    };
+    show("temperature", requested.temperature.map(|value| value.to_string()));
+    show("top_p", requested.top_p.map(|value| value.to_string()));
+    show("repeat_penalty", requested.repeat_penalty.map(|value| value.to_string()));
+    show("num_ctx", requested.num_ctx.map(|value| value.to_string()));
+
+    if let (Some(num_ctx), Some(context_length)) = (requested.num_ctx, context_length) {
+        if num_ctx > context_length {
+            logging::info(&format!(
+                "{} num_ctx: requested {}, effective {} (clamped to the model's trained context length)",
+                status::warn(), num_ctx, context_length
+            ));
+        }
+    }
+    if requested.system.is_some() {
+        logging::info("system: custom system prompt (requested)");
+    }
+
+    for (name, value) in &defaults {
+        logging::info(&format!("{}: {} (model default)", name, value));
+    }
+}
@@ -0,0 +1,69 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::client;
+
+// Connection profiles live next to .env in profiles.toml, e.g.
+//
+// [gpu-box]
+// host = "192.168.1.50"
+// port = 11434
+// model = "llama3.1:70b"
+// https = false
+// auth = "Bearer my-token"
+const PROFILES_FILE: &str = "profiles.toml";
+
+// A named set of connection settings; every field is optional
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Profile {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub model: Option<String>,
+    pub https: Option<bool>,
+    pub auth: Option<String>,
+}
+
+// Function to read all profiles (an empty map if the file doesn't exist)
+pub fn load_profiles() -> Result<BTreeMap<String, Profile>, Box<dyn std::error::Error>> {
+    let path = Path::new(PROFILES_FILE);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let contents = fs::read_to_string(path)?;
+    let profiles = toml::from_str(&contents)
+        .map_err(|e| format!("Could not parse {}: {}", PROFILES_FILE, e))?;
+    Ok(profiles)
+}
+
+// Function to look up a single profile by name
+pub fn load_profile(name: &str) -> Result<Profile, Box<dyn std::error::Error>> {
+    let mut profiles = load_profiles()?;
+    profiles
+        .remove(name)
+        .ok_or_else(|| format!("Profile '{}' not found in {}", name, PROFILES_FILE).into())
+}
+
+// Function to print the available profiles for --list-profiles
+pub fn list_profiles() -> Result<(), Box<dyn std::error::Error>> {
+    let profiles = load_profiles()?;
+
+    if profiles.is_empty() {
+        println!("No profiles found. Create {} to define some.", PROFILES_FILE);
+        return Ok(());
+    }
+
+    println!("Available profiles:");
+    for (name, profile) in &profiles {
+        let https = profile.https.unwrap_or(false);
+        let scheme = if https { "https" } else { "http" };
+        let host = profile.host.as_deref().unwrap_or("(server_ip from .env)");
+        let port = profile.port.unwrap_or(client::default_port(https));
+        let model = profile.model.as_deref().unwrap_or("(default)");
+        println!("  - {}: {}://{}:{} model={}", name, scheme, host, port, model);
+    }
+
+    Ok(())
+}
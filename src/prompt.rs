@@ -0,0 +1,220 @@
+use ollama_rs::Ollama;
+
+use crate::{logging, options, settings, status};
+
+// Total size of all --context-file contents inlined into a prompt
+const MAX_CONTEXT_CHARS: usize = 100_000;
+
+// Files larger than this need --force before they are sent as (part of) a prompt
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+// Context window Ollama uses when neither the model nor --num-ctx sets one
+const DEFAULT_NUM_CTX: u64 = 4096;
+
+// Prefix for an inline model switch, e.g. "@model:mistral summarize this"
+const MODEL_DIRECTIVE: &str = "@model:";
+
+// Function to take an inline @model:<name> directive off the start of a prompt.
+// Only honored with --inline-directives, since ordinary prompts can start with "@".
+// Returns the model to use for this one request, if any, and the prompt without the directive.
+pub fn take_model_directive(prompt: String) -> (Option<String>, String) {
+    if !settings::get().inline_directives {
+        return (None, prompt);
+    }
+
+    let Some(rest) = prompt.trim_start().strip_prefix(MODEL_DIRECTIVE) else {
+        return (None, prompt);
+    };
+    let (model, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if model.is_empty() {
+        return (None, prompt);
+    }
+
+    (Some(model.to_string()), rest.trim_start().to_string())
+}
+
+// Function to apply the prompt transformations enabled on the command line
+pub fn prepare(prompt: String) -> String {
+    prepare_turn(prompt, true)
+}
+
+// Function to prepare one prompt of a conversation. --context-file contents are only inlined
+// into the first one (`first`); later turns already carry them in the conversation context.
+pub fn prepare_turn(prompt: String, first: bool) -> String {
+    let mut prompt = prompt;
+
+    if settings::get().expand_env {
+        prompt = expand_env(&prompt);
+    }
+
+    // Fixed instructions around every prompt, e.g. --prompt-suffix "Respond in JSON."
+    let settings = settings::get();
+    if let Some(prefix) = settings.prompt_prefix.clone().or_else(|| settings::env_var("prompt_prefix").ok()) {
+        prompt = format!("{}\n\n{}", prefix, prompt);
+    }
+    if let Some(suffix) = settings.prompt_suffix.clone().or_else(|| settings::env_var("prompt_suffix").ok()) {
+        prompt = format!("{}\n\n{}", prompt, suffix);
+    }
+
+    if let Some(context) = settings::get().file_context.as_ref().filter(|_| first) {
+        prompt = format!("{}\n{}", context, prompt);
+    }
+
+    prompt
+}
+
+// Function to stop accidental huge inputs, e.g. a log file or binary passed by mistake.
+// Checks the size on disk before anything is read; --force skips the check.
+pub fn check_file_size(path: &str, force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let size = std::fs::metadata(path)
+        .map_err(|e| format!("Could not read {}: {}", path, e))?
+        .len();
+
+    if size > MAX_FILE_BYTES && !force {
+        let megabytes = size as f64 / (1024.0 * 1024.0);
+        return Err(format!("{} is {:.1}MB; use --force to send it anyway.", path, megabytes).into());
+    }
+
+    Ok(())
+}
+
+// Function to read --prompt-file as the prompt
+pub fn read_prompt_file(path: &str, force: bool) -> Result<String, Box<dyn std::error::Error>> {
+    check_file_size(path, force)?;
+    let prompt = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read prompt file {}: {}", path, e))?;
+    Ok(prompt.trim_end().to_string())
+}
+
+// Function to read --context-file files into one block, each under a "=== file: name ===" header.
+// Contents beyond the size limit are cut off. This runs before the settings are set, so the
+// warnings about cut-off files are returned for the caller to print instead of printed here.
+pub fn load_context_files(paths: &[String], force: bool) -> Result<(Option<String>, Vec<String>), Box<dyn std::error::Error>> {
+    let mut warnings = Vec::new();
+    if paths.is_empty() {
+        return Ok((None, warnings));
+    }
+
+    let mut block = String::new();
+    let mut remaining = MAX_CONTEXT_CHARS;
+
+    for path in paths {
+        check_file_size(path, force)?;
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read context file {}: {}", path, e))?;
+        let length = contents.chars().count();
+
+        let contents = if length > remaining {
+            warnings.push(format!("{} truncated to {} of {} characters to fit the {}-character limit", path, remaining, length, MAX_CONTEXT_CHARS));
+            contents.chars().take(remaining).collect()
+        } else {
+            contents
+        };
+        remaining -= contents.chars().count();
+
+        block.push_str(&format!("=== file: {} ===\n{}\n", path, contents.trim_end()));
+    }
+
+    Ok((Some(block), warnings))
+}
+
+// Function to replace ${VAR} references with values from the environment.
+// Unknown variables are left as-is and reported on stderr so typos don't silently vanish.
+pub fn expand_env(prompt: &str) -> String {
+    let mut output = String::new();
+    let mut rest = prompt;
+
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+
+        let Some(end) = after.find('}') else {
+            output.push_str(&rest[start..]);
+            return output;
+        };
+
+        let name = &after[..end];
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => output.push_str(&value),
+            _ => {
+                eprintln!("{} Unknown variable ${{{}}} left unchanged", status::warn(), name);
+                output.push_str(&rest[start..start + end + 3]);
+            }
+        }
+
+        rest = &after[end + 1..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+// Function to find the model's context window: --num-ctx, then model_defaults.toml,
+// then num_ctx from the modelfile, then the default
+pub async fn context_window(ollama: &Ollama, model: &str) -> u64 {
+    if let Some(num_ctx) = settings::get().num_ctx.or(options::defaults_for(model).num_ctx) {
+        return num_ctx;
+    }
+
+    let parameters = match ollama.show_model_info(model.to_string()).await {
+        Ok(info) => info.parameters,
+        Err(e) => {
+            logging::debug(&format!("could not read model info for {}: {}", model, e));
+            return DEFAULT_NUM_CTX;
+        }
+    };
+
+    // Parameters are listed one per line, e.g. "num_ctx    8192"
+    parameters
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("num_ctx"))
+        .find_map(|value| value.trim().parse().ok())
+        .unwrap_or(DEFAULT_NUM_CTX)
+}
+
+// Function to warn (for --check-context) when a prompt alone won't fit in the context window,
+// since the server then silently drops its beginning. Uses ~4 characters per token.
+pub async fn check_context(ollama: &Ollama, model: &str, prompt: &str) {
+    if !settings::get().check_context {
+        return;
+    }
+
+    let estimated_tokens = (prompt.chars().count() as u64).div_ceil(4);
+    let num_ctx = context_window(ollama, model).await;
+    logging::debug(&format!("prompt is ~{} tokens, context window is {}", estimated_tokens, num_ctx));
+
+    if estimated_tokens > num_ctx {
+        logging::info(&format!(
+            "{} Prompt is ~{} tokens but {} has a {}-token context window; the beginning will be truncated",
+            status::warn(), estimated_tokens, model, num_ctx
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_env_replaces_a_known_variable() {
+        // SAFETY: no other test reads or writes this variable
+        unsafe { std::env::set_var("PROMPT_TEST_PROJECT", "ollama-cli") };
+        assert_eq!(expand_env("Review ${PROMPT_TEST_PROJECT} for bugs"), "Review ollama-cli for bugs");
+    }
+
+    #[test]
+    fn expand_env_keeps_an_unknown_variable() {
+        assert_eq!(expand_env("Hello ${PROMPT_TEST_MISSING}!"), "Hello ${PROMPT_TEST_MISSING}!");
+    }
+
+    #[test]
+    fn expand_env_keeps_an_empty_name() {
+        assert_eq!(expand_env("costs ${} today"), "costs ${} today");
+    }
+
+    #[test]
+    fn expand_env_keeps_an_unterminated_reference() {
+        assert_eq!(expand_env("price in ${CURRENCY"), "price in ${CURRENCY");
+        assert_eq!(expand_env("$${"), "$${");
+    }
+}
@@ -0,0 +1,279 @@
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ollama_rs::generation::completion::{GenerationContext, request::GenerationRequest};
+use std::io::{stdin, IsTerminal};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+use crate::connecttoollama::get_user_input;
+use crate::metrics::Metrics;
+use crate::sessions::{self, Session, Turn};
+use crate::{client, error, logging, modelmanager, options, prompt, settings, status, streaming};
+
+// Sent for /continue; the saved context already holds the previous answer
+const CONTINUE_PROMPT: &str = "Continue exactly where you left off, without repeating anything.";
+
+// The last prompt sent, with the context it was sent with, so /regen can send it again.
+// `raw` is the prompt as typed, which is what the saved session keeps.
+struct LastRequest {
+    prompt: String,
+    raw: String,
+    context: Option<GenerationContext>,
+    model: Option<String>,
+}
+
+// Function to pick a fresh sampling seed for /regen new
+fn fresh_seed() -> i32 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    (nanos & i32::MAX as u32) as i32
+}
+
+fn print_help() {
+    println!("Commands:");
+    println!("  /continue  Extend the previous response");
+    println!("  /regen     Ask the previous prompt again for a different answer (/regen new uses a fresh seed)");
+    println!("  /cancel    Stop the response that is streaming (or just press Enter)");
+    println!("  /tag NAME  Tag the saved session (--session) by project or topic; /tag alone lists its tags");
+    println!("  /help      Show this help");
+    println!("  /exit      Leave the session");
+}
+
+// Function to watch the terminal for /cancel (or a bare Enter) while a response streams.
+// It polls with a short timeout and stops reading once `stop` is set, so anything typed
+// afterwards is left for the next prompt. Resolves to true if the user cancelled.
+fn watch_for_cancel(stop: Arc<AtomicBool>) -> JoinHandle<bool> {
+    tokio::task::spawn_blocking(move || {
+        let mut line = String::new();
+
+        while !stop.load(Ordering::SeqCst) {
+            match event::poll(Duration::from_millis(50)) {
+                Ok(true) => {},
+                Ok(false) => continue,
+                Err(_) => return false,
+            }
+
+            match event::read() {
+                Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Enter => {
+                        let typed = line.trim();
+                        if typed.is_empty() || typed == "/cancel" {
+                            return true;
+                        }
+                        line.clear();
+                    },
+                    KeyCode::Char(c) => line.push(c),
+                    _ => {},
+                },
+                Ok(_) => {},
+                Err(_) => return false,
+            }
+        }
+
+        false
+    })
+}
+
+// Function to run an interactive session where each prompt builds on the previous ones.
+// Returns the last answer, which is what --output, --clipboard and the post-hook receive.
+pub async fn run_repl(local: bool) -> Result<Option<Metrics>, Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+
+    let model = settings::model_for(local);
+    let (ollama, url) = client::build_client(local)?;
+    println!("Connecting to: {}", url);
+    println!("Using model: {}", model);
+    println!("Type a prompt, or /help for commands.");
+
+    let mut context: Option<GenerationContext> = None;
+    let mut last: Option<LastRequest> = None;
+    let mut answer: Option<Metrics> = None;
+
+    // With --session the conversation is saved after every answer and resumed next time
+    let session_name = settings::get().session.clone();
+    let mut session: Option<Session> = match &session_name {
+        Some(name) => {
+            let saved = sessions::load(name)?;
+            match &saved {
+                Some(saved) if saved.model == model => {
+                    context = saved.context.clone();
+                    println!("Resumed session '{}' ({} turns)", name, saved.turns.len());
+                },
+                Some(saved) => println!("{} Session '{}' was started with {}; its context is not carried over", status::warn(), name, saved.model),
+                None => println!("Saving this session as '{}'", name),
+            }
+            Some(saved.unwrap_or_default())
+        },
+        None => None,
+    };
+
+    loop {
+        // End of input (Ctrl-D or a closed pipe) ends the session like /exit
+        let Some(input) = get_user_input("\n> ") else {
+            break;
+        };
+
+        // The answer builds on the conversation so far, unless /regen replaces the last answer
+        let mut base_context = context.clone();
+        let mut seed = None;
+        // Set by an @model:<name> directive (--inline-directives) for this request only
+        let mut model_override = None;
+        let regenerating = matches!(input.as_str(), "/regen" | "/regen new");
+        // A typed prompt still needs preparing; /regen resends exactly what was sent before
+        let mut typed = false;
+        let mut resend = None;
+
+        let raw = match input.as_str() {
+            "" => continue,
+            "/exit" | "/quit" => break,
+            "/help" => {
+                print_help();
+                continue;
+            },
+            "/continue" => {
+                if context.is_none() {
+                    println!("Nothing to continue yet.");
+                    continue;
+                }
+                CONTINUE_PROMPT.to_string()
+            },
+            "/regen" | "/regen new" => {
+                let Some(previous) = &last else {
+                    println!("Nothing to regenerate yet.");
+                    continue;
+                };
+                if input == "/regen new" {
+                    seed = Some(fresh_seed());
+                }
+                base_context = previous.context.clone();
+                model_override = previous.model.clone();
+                resend = Some(previous.prompt.clone());
+                previous.raw.clone()
+            },
+            command if command == "/tag" || command.starts_with("/tag ") => {
+                let (Some(name), Some(session)) = (&session_name, session.as_mut()) else {
+                    println!("Tags belong to saved sessions. Start with --session <name> to save this one.");
+                    continue;
+                };
+                let tag = command["/tag".len()..].trim();
+                if tag.is_empty() {
+                    println!("Tags: {}", if session.tags.is_empty() { "(none)".to_string() } else { session.tags.join(", ") });
+                    continue;
+                }
+                if !sessions::add_tag(session, tag) {
+                    println!("Session '{}' is already tagged '{}'.", name, tag.to_lowercase());
+                    continue;
+                }
+                match sessions::save(name, session) {
+                    Ok(()) => println!("{} Tagged session '{}' with '{}'", status::ok(), name, tag.to_lowercase()),
+                    Err(e) => println!("{} Could not save session: {}", status::err(), e),
+                }
+                continue;
+            },
+            command if command.starts_with('/') => {
+                println!("{} Unknown command {}. Type /help for commands.", status::err(), command);
+                continue;
+            },
+            _ => {
+                let (directive, text) = prompt::take_model_directive(input);
+                model_override = directive;
+                typed = true;
+                text
+            },
+        };
+
+        // The conversation context belongs to the session's model, so another model starts fresh
+        if model_override.is_some() {
+            base_context = None;
+        }
+        let request_model = model_override.clone().unwrap_or_else(|| model.clone());
+        if model_override.is_some() {
+            println!("(using {} for this request)", request_model);
+        }
+
+        // --context-file contents only go into the first prompt of a conversation; after that
+        // the context already holds them
+        let user_prompt = match resend {
+            Some(sent) => sent,
+            None if typed => prompt::prepare_turn(raw.clone(), base_context.is_none()),
+            None => raw.clone(),
+        };
+
+        prompt::check_context(&ollama, &request_model, &user_prompt).await;
+        let request_id = logging::start_request(&user_prompt);
+        last = Some(LastRequest { prompt: user_prompt.clone(), raw, context: base_context.clone(), model: model_override.clone() });
+        let mut request = options::apply_seeded(GenerationRequest::new(request_model.clone(), user_prompt), &request_model, seed);
+        if let Some(context) = base_context {
+            request = request.context(context);
+        }
+
+        // The cancel watcher reads the terminal while the answer streams, so a missing model
+        // is offered for pulling now rather than from inside the generation. Piped input never
+        // gets the question: it would take the next prompt as the answer.
+        let watch = stdin().is_terminal();
+        if watch {
+            if let Err(e) = modelmanager::ensure_installed(&ollama, &url, &request_model).await {
+                println!("{} Error: {}", status::err(), e);
+                continue;
+            }
+        }
+
+        let generation = streaming::generate(&ollama, local, request, &request_model, &request_id, false);
+
+        // Race the generation against the cancel watcher (terminals only)
+        let result = if watch {
+            let stop = Arc::new(AtomicBool::new(false));
+            let mut watcher = watch_for_cancel(stop.clone());
+
+            let result = tokio::select! {
+                result = generation => Some(result),
+                Ok(true) = &mut watcher => None,
+            };
+            stop.store(true, Ordering::SeqCst);
+            result
+        } else {
+            Some(generation.await)
+        };
+
+        // A cancelled or failed generation ends that answer, not the whole session
+        let metrics = match result {
+            None => {
+                println!("\n{} Generation cancelled", status::warn());
+                continue;
+            },
+            Some(Ok(metrics)) => metrics,
+            Some(Err(e)) => {
+                println!("\n{} Error: {}", status::err(), error::explain(e, &url));
+                continue;
+            }
+        };
+        metrics.print(settings::get().metrics_format.as_deref());
+
+        if metrics.context.is_some() && model_override.is_none() {
+            context = metrics.context.clone();
+        }
+
+        if let (Some(name), Some(session)) = (&session_name, session.as_mut()) {
+            if model_override.is_none() {
+                // A regenerated answer replaces the previous one
+                if regenerating {
+                    session.turns.pop();
+                }
+                session.model = model.clone();
+                session.context = context.clone();
+                session.turns.push(Turn { prompt: last.as_ref().map(|last| last.raw.clone()).unwrap_or_default(), response: metrics.response.clone() });
+                if let Err(e) = sessions::save(name, session) {
+                    println!("{} Could not save session: {}", status::warn(), e);
+                }
+            }
+        }
+
+        // The server ends with done_reason "length" when num_predict or the context ran out
+        if metrics.done_reason.as_deref() == Some("length") {
+            println!("(The response was cut off by the length limit. Type /continue to keep going.)");
+        }
+        answer = Some(metrics);
+    }
+
+    Ok(answer)
+}
@@ -0,0 +1,111 @@
+use ollama_rs::generation::completion::GenerationContext;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::logging;
+
+// Saved interactive sessions live in ./sessions/<name>.json
+const SESSIONS_DIR: &str = "sessions";
+
+// One prompt and its answer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Turn {
+    pub prompt: String,
+    pub response: String,
+}
+
+// A saved session. Every field has a default and unknown fields are ignored, so files
+// written by older or newer versions still load.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub model: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub tags: Vec<String>,
+    pub context: Option<GenerationContext>,
+    pub turns: Vec<Turn>,
+}
+
+// Function to get a session's file path, refusing names that would leave ./sessions/
+fn session_path(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("Invalid session name '{}': use letters, digits, - and _", name).into());
+    }
+    Ok(Path::new(SESSIONS_DIR).join(format!("{}.json", name)))
+}
+
+// Function to load a saved session, or None if it doesn't exist yet
+pub fn load(name: &str) -> Result<Option<Session>, Box<dyn std::error::Error>> {
+    let path = session_path(name)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents = fs::read_to_string(&path)?;
+    let session = serde_json::from_str(&contents)
+        .map_err(|e| format!("Could not parse {}: {}", path.display(), e))?;
+    Ok(Some(session))
+}
+
+// Function to write a session, stamping when it was last updated
+pub fn save(name: &str, session: &mut Session) -> Result<(), Box<dyn std::error::Error>> {
+    let path = session_path(name)?;
+    fs::create_dir_all(SESSIONS_DIR)?;
+
+    session.updated_at = logging::timestamp();
+    if session.created_at.is_empty() {
+        session.created_at = session.updated_at.clone();
+    }
+    fs::write(&path, serde_json::to_string_pretty(session)?)
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+// Function to add a tag (case-insensitive, no duplicates); returns false if it was already there
+pub fn add_tag(session: &mut Session, tag: &str) -> bool {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() || session.tags.contains(&tag) {
+        return false;
+    }
+    session.tags.push(tag);
+    session.tags.sort();
+    true
+}
+
+// Function to handle --sessions-by-tag: list the saved sessions carrying a tag
+pub fn list_by_tag(tag: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let tag = tag.trim().to_lowercase();
+    let mut names = Vec::new();
+
+    if let Ok(entries) = fs::read_dir(SESSIONS_DIR) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            // A broken file shouldn't hide the others
+            if let Ok(Some(session)) = load(name) {
+                if session.tags.contains(&tag) {
+                    names.push((name.to_string(), session));
+                }
+            }
+        }
+    }
+
+    if names.is_empty() {
+        println!("No sessions tagged '{}'.", tag);
+        return Ok(());
+    }
+
+    names.sort_by(|a, b| b.1.updated_at.cmp(&a.1.updated_at));
+    println!("Sessions tagged '{}':", tag);
+    for (name, session) in names {
+        println!("  {:<20} {} turns, {}, updated {} [{}]", name, session.turns.len(), session.model, session.updated_at, session.tags.join(", "));
+    }
+    Ok(())
+}
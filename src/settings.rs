@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::client::ConnectionPolicy;
+use crate::error::ClientError;
+use crate::export::ResponseFormat;
+use crate::options::{ModelDefaults, Preset};
+use crate::profiles::Profile;
+
+// Options chosen on the command line that apply to every generation in this run
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub metrics_format: Option<String>,
+    pub wrap: Option<usize>,
+    pub expand_env: bool,
+    pub dump_image_payload: Option<String>,
+    pub image_prompt: Option<String>,
+    pub ascii: bool,
+    pub verbose: bool,
+    pub profile: Option<Profile>,
+    pub profile_name: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub model: Option<String>,
+    pub follow_symlinks: bool,
+    pub stream_json: bool,
+    pub detect_loops: bool,
+    pub abort_on_loop: bool,
+    pub headers: Vec<(String, String)>,
+    pub number_code: bool,
+    pub env_files: Vec<String>,
+    pub clipboard: bool,
+    pub quiet: bool,
+    pub check_context: bool,
+    pub num_ctx: Option<u64>,
+    pub retry_on_empty: u32,
+    pub file_context: Option<String>,
+    pub echo_prompt: bool,
+    pub policy: Option<ConnectionPolicy>,
+    pub temperature: Option<f32>,
+    pub system: Option<String>,
+    pub model_defaults: BTreeMap<String, ModelDefaults>,
+    pub https: bool,
+    pub retries: u32,
+    pub prompt_prefix: Option<String>,
+    pub prompt_suffix: Option<String>,
+    pub lang: Option<String>,
+    pub output: Option<String>,
+    pub format_response: ResponseFormat,
+    pub keep_alive: Option<String>,
+    pub strip_ansi: bool,
+    pub buffered: bool,
+    pub force: bool,
+    pub save_fetched: bool,
+    pub inline_directives: bool,
+    pub save_response_json: Option<String>,
+    pub pager: bool,
+    pub wait_for_server: Option<u64>,
+    pub show_params: bool,
+    pub post_hook: Option<String>,
+    pub post_hook_file: Option<String>,
+    pub timing_bar: bool,
+    pub stop_regex: Option<String>,
+    pub preset: Option<Preset>,
+    pub preset_name: Option<String>,
+    pub live_metrics: bool,
+    pub session: Option<String>,
+    pub max_image_bytes: u64,
+    pub self_critique: bool,
+    pub max_gen_time: Option<u64>,
+    pub unload_on_exit: bool,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+// Set by --no-env before anything reads the environment, so it lives outside Settings
+static NO_ENV: AtomicBool = AtomicBool::new(false);
+
+// Function to store the settings once at startup. Anything that read them earlier got the
+// defaults and filled the slot, which would silently drop every flag, so that is a bug.
+pub fn init(settings: Settings) {
+    if SETTINGS.set(settings).is_err() {
+        panic!("settings::init called after the settings were already read");
+    }
+}
+
+// Function to read the settings (defaults if init was never called)
+pub fn get() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+// Function to ignore .env and config variables for the rest of the run (--no-env)
+pub fn disable_env() {
+    NO_ENV.store(true, Ordering::Relaxed);
+}
+
+// Function to load .env from the working directory unless --no-env was given
+pub fn load_dotenv() {
+    if !NO_ENV.load(Ordering::Relaxed) {
+        dotenv::dotenv().ok();
+    }
+}
+
+// Function to load --env-file layers so that later files override earlier ones.
+// dotenv never overwrites a variable that is already set, so the files are loaded
+// last-first; the plain .env loaded afterwards only fills in what is still missing.
+pub fn load_env_files(paths: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    for path in paths.iter().rev() {
+        dotenv::from_path(path).map_err(|e| format!("Could not load env file {}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+// Function to read a config variable, treating empty or whitespace-only values as unset
+// so a line like `model=` in .env falls back to the default instead of sending "".
+pub fn env_var(name: &str) -> Result<String, std::env::VarError> {
+    if NO_ENV.load(Ordering::Relaxed) {
+        return Err(std::env::VarError::NotPresent);
+    }
+
+    let value = std::env::var(name)?;
+    let trimmed = value.trim();
+
+    if trimmed.is_empty() {
+        Err(std::env::VarError::NotPresent)
+    } else {
+        Ok(trimmed.to_string())
+    }
+}
+
+// Function to read a variable that must be set, with an error that says what went wrong
+pub fn require_env(name: &str) -> Result<String, ClientError> {
+    env_var(name).map_err(|cause| ClientError::MissingEnv {
+        name: name.to_string(),
+        cause,
+    })
+}
+
+// Function to pick the text model for the connection the policy prefers (see model_for)
+pub fn text_model() -> String {
+    model_for(crate::client::prefer_local())
+}
+
+// Function to get the text model for a local or remote connection: --model, then the profile
+// (remote only), then local_model / remote_model, then model from .env, then the default.
+// Lets a laptop run a small model while the GPU server runs a big one.
+pub fn model_for(local: bool) -> String {
+    let settings = get();
+    let profile_model = settings.profile.as_ref()
+        .and_then(|profile| profile.model.clone())
+        .filter(|_| !local);
+    let connection_key = if local { "local_model" } else { "remote_model" };
+
+    settings.model.clone()
+        .or(profile_model)
+        .or_else(|| env_var(connection_key).ok())
+        .or_else(|| env_var("model").ok())
+        .unwrap_or_else(|| "llama3.2".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test uses its own variable names, since the environment is shared between test threads
+    fn set(name: &str, value: &str) {
+        // SAFETY: no other test reads or writes these variables
+        unsafe { std::env::set_var(name, value) };
+    }
+
+    #[test]
+    fn env_var_treats_an_empty_value_as_unset() {
+        set("SETTINGS_TEST_EMPTY", "");
+        assert_eq!(env_var("SETTINGS_TEST_EMPTY"), Err(std::env::VarError::NotPresent));
+    }
+
+    #[test]
+    fn env_var_treats_whitespace_as_unset() {
+        set("SETTINGS_TEST_BLANK", "   ");
+        assert_eq!(env_var("SETTINGS_TEST_BLANK"), Err(std::env::VarError::NotPresent));
+    }
+
+    #[test]
+    fn env_var_trims_a_real_value() {
+        set("SETTINGS_TEST_MODEL", "  llama3.2:1b ");
+        assert_eq!(env_var("SETTINGS_TEST_MODEL"), Ok("llama3.2:1b".to_string()));
+    }
+
+    #[test]
+    fn require_env_names_a_missing_variable() {
+        let error = require_env("SETTINGS_TEST_MISSING").unwrap_err();
+        assert!(matches!(error, ClientError::MissingEnv { cause: std::env::VarError::NotPresent, .. }));
+        assert_eq!(error.to_string(), "SETTINGS_TEST_MISSING must be set in .env file");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn require_env_reports_invalid_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        // SAFETY: no other test reads or writes this variable
+        unsafe { std::env::set_var("SETTINGS_TEST_NOT_UNICODE", OsStr::from_bytes(b"llama\xff")) };
+        let error = require_env("SETTINGS_TEST_NOT_UNICODE").unwrap_err();
+        assert!(matches!(error, ClientError::MissingEnv { cause: std::env::VarError::NotUnicode(_), .. }));
+        assert_eq!(error.to_string(), "SETTINGS_TEST_NOT_UNICODE in .env file is not valid UTF-8");
+    }
+}
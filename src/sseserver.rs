@@ -0,0 +1,107 @@
+use ollama_rs::generation::completion::request::GenerationRequest;
+use reqwest::Url;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use crate::streaming::Sink;
+use crate::{client, error, logging, options, prompt, settings, status, streaming};
+
+// Errors inside connection tasks must be Send to cross tokio::spawn
+type ConnectionError = Box<dyn std::error::Error + Send + Sync>;
+
+// Function to serve generations as Server-Sent Events: GET /generate?prompt=... streams one
+// `data:` event per chunk (the --stream-json delta format), then `event: done` with the metrics
+pub async fn serve(addr: String, local: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(&addr).await?;
+    println!("{} SSE server listening on http://{}/generate?prompt=...", status::ok(), addr);
+    println!("Using model: {}", settings::model_for(local));
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        logging::debug(&format!("SSE client connected: {}", peer));
+
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, local).await {
+                println!("{} SSE client {}: {}", status::warn(), peer, e);
+            }
+        });
+    }
+}
+
+// Function to read the request line and headers, returning the prompt from the query string
+async fn read_prompt(reader: &mut BufReader<TcpStream>) -> Result<Option<String>, ConnectionError> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+
+    // Skip the headers; only the query string matters
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some("GET"), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+
+    let url = Url::parse(&format!("http://localhost{}", target))?;
+    if url.path() != "/generate" {
+        return Ok(None);
+    }
+
+    Ok(url.query_pairs()
+        .find(|(key, _)| key == "prompt")
+        .map(|(_, value)| value.into_owned()))
+}
+
+// Function to answer a single request with an event stream
+async fn handle_connection(stream: TcpStream, local: bool) -> Result<(), ConnectionError> {
+    let mut reader = BufReader::new(stream);
+
+    let Some(user_prompt) = read_prompt(&mut reader).await? else {
+        let body = "Use GET /generate?prompt=...\n";
+        let response = format!("HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+        reader.get_mut().write_all(response.as_bytes()).await?;
+        return Ok(());
+    };
+    let socket = reader.get_mut();
+
+    socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\nAccess-Control-Allow-Origin: *\r\n\r\n").await?;
+
+    let (ollama, server) = client::build_client(local).map_err(|e| e.to_string())?;
+    let model = settings::model_for(local);
+    let user_prompt = prompt::prepare(user_prompt);
+    let request_id = logging::start_request(&user_prompt);
+    let request = options::apply(GenerationRequest::new(model.clone(), user_prompt), &model);
+
+    // Same generation path as the terminal (retries, stop conditions); the text comes back
+    // over a channel and is written out as events while the answer streams
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let generation = async {
+        let sink = Sink::Channel(sender);
+        streaming::generate_into(&ollama, local, request, &model, &request_id, &sink).await
+            .map_err(|e| error::explain(e, &server).to_string())
+    };
+    let forward = async {
+        while let Some(text) = receiver.recv().await {
+            let event = format!("data: {}\n\n", streaming::delta_message(&text));
+            socket.write_all(event.as_bytes()).await?;
+            socket.flush().await?;
+        }
+        Ok::<_, ConnectionError>(())
+    };
+    let (result, forwarded) = tokio::join!(generation, forward);
+    forwarded?;
+
+    let event = match result {
+        Ok(metrics) => format!("event: done\ndata: {}\n\n", streaming::done_message(&metrics)),
+        Err(e) => format!("event: error\ndata: {}\n\n", serde_json::json!({ "error": e })),
+    };
+    socket.write_all(event.as_bytes()).await?;
+    socket.flush().await?;
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use crate::settings;
+
+// Status markers used in messages. Emoji render as boxes on some Windows terminals
+// and in CI logs, so --ascii or ASCII_ONLY=1 swaps them for plain text.
+fn ascii_only() -> bool {
+    settings::get().ascii
+        || settings::env_var("ASCII_ONLY")
+            .map(|value| value != "0")
+            .unwrap_or(false)
+}
+
+pub fn ok() -> &'static str {
+    if ascii_only() { "[OK]" } else { "✅" }
+}
+
+pub fn err() -> &'static str {
+    if ascii_only() { "[ERR]" } else { "❌" }
+}
+
+pub fn warn() -> &'static str {
+    if ascii_only() { "[WARN]" } else { "⚠️ " }
+}
+
+pub fn retry() -> &'static str {
+    if ascii_only() { "[RETRY]" } else { "🔄" }
+}
+
+pub fn tool() -> &'static str {
+    if ascii_only() { "[TOOL]" } else { "🔧" }
+}
+
+pub fn result() -> &'static str {
+    if ascii_only() { "->" } else { "↳" }
+}
+
+pub fn bye() -> &'static str {
+    if ascii_only() { "[BYE]" } else { "👋" }
+}
@@ -0,0 +1,516 @@
+use futures_util::stream::{self, Stream};
+use ollama_rs::Ollama;
+use ollama_rs::generation::completion::{GenerationResponse, request::GenerationRequest};
+use regex::Regex;
+use serde_json::{json, Value};
+use tokio::io::{self, AsyncWriteExt};
+use tokio_stream::StreamExt;
+use std::io::IsTerminal;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::ansistrip::AnsiStripper;
+use crate::livemetrics::LiveMetrics;
+use crate::codenumber::CodeNumberer;
+use crate::metrics::Metrics;
+use crate::loopdetect::LoopDetector;
+use crate::error::{self, ErrorClass};
+use crate::{client, loading, logging, modelmanager, pager, settings, status, warmup};
+use crate::wordwrap::WordWrapper;
+
+// How much already-checked output --stop-regex looks at again with each new chunk, so matches
+// spanning a chunk boundary are found without rescanning the whole response every time
+const STOP_REGEX_LOOKBACK: usize = 1024;
+
+// Function to check a --stop-regex pattern while parsing arguments
+pub fn validate_stop_regex(pattern: &str) -> Result<String, String> {
+    Regex::new(pattern)
+        .map(|_| pattern.to_string())
+        .map_err(|e| format!("invalid regex: {}", e))
+}
+
+// With --buffered, output is flushed once this much is pending or this much time has passed
+const FLUSH_BYTES: usize = 8 * 1024;
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+// Function to build the JSON message for one streamed chunk (--stream-json, --serve-ws)
+pub fn delta_message(text: &str) -> String {
+    json!({ "delta": text, "done": false }).to_string()
+}
+
+// Function to build the final JSON message carrying the metrics
+pub fn done_message(metrics: &Metrics) -> String {
+    json!({ "done": true, "metrics": metrics }).to_string()
+}
+
+// Where stream_response sends the answer: the terminal, with all the display options, or a
+// channel that hands the raw text chunks to a server's client (--serve-ws, --sse)
+pub enum Sink {
+    Terminal,
+    Channel(UnboundedSender<String>),
+}
+
+// Response chunks arriving in batches, like ollama-rs's GenerationResponseStream
+pub type ChunkStream = Pin<Box<dyn Stream<Item = Result<Vec<GenerationResponse>, std::io::Error>> + Send>>;
+
+// Where a raw stream leaves the server's done_reason once the final chunk arrives
+pub type DoneReason = Arc<Mutex<Option<String>>>;
+
+// Function to start a generation stream straight from /api/generate. ollama-rs's typed chunks
+// drop the server's done_reason ("stop", "length", ...), which tells a length cut-off apart
+// from a natural end, so the lines are parsed here and done_reason is kept on the side.
+pub async fn open_generate_stream(local: bool, request: &GenerationRequest<'_>) -> Result<(ChunkStream, DoneReason), Box<dyn std::error::Error>> {
+    let (http_client, base_url) = client::build_http_client(local)?;
+    let mut body = serde_json::to_value(request)?;
+    body["stream"] = json!(true);
+
+    let response = http_client
+        .post(format!("{}/api/generate", base_url))
+        .json(&body)
+        .send()
+        .await?;
+    let response = error::check_response(response).await?;
+
+    let done_reason = DoneReason::default();
+    let slot = done_reason.clone();
+    let chunks = stream::unfold(Some((response, Vec::new())), move |state| {
+        let slot = slot.clone();
+        async move {
+            let (mut response, mut pending) = state?;
+            loop {
+                let ended = match response.chunk().await {
+                    Ok(Some(bytes)) => {
+                        pending.extend_from_slice(&bytes);
+                        false
+                    },
+                    // A last line without a newline still counts
+                    Ok(None) => {
+                        pending.push(b'\n');
+                        true
+                    },
+                    Err(e) => return Some((Err(std::io::Error::other(e)), None)),
+                };
+
+                match parse_lines(&mut pending, &slot) {
+                    Ok(batch) if batch.is_empty() && ended => return None,
+                    Ok(batch) if batch.is_empty() => continue,
+                    Ok(batch) if ended => return Some((Ok(batch), None)),
+                    Ok(batch) => return Some((Ok(batch), Some((response, pending)))),
+                    Err(e) => return Some((Err(e), None)),
+                }
+            }
+        }
+    });
+
+    Ok((Box::pin(chunks), done_reason))
+}
+
+// Function to take the complete lines off the front of a raw stream buffer and parse each one
+// (a JSON object) into a chunk, noting the done_reason. An {"error": ...} line ends the stream.
+fn parse_lines(pending: &mut Vec<u8>, done_reason: &DoneReason) -> Result<Vec<GenerationResponse>, std::io::Error> {
+    let mut batch = Vec::new();
+
+    while let Some(newline) = pending.iter().position(|&byte| byte == b'\n') {
+        let line: Vec<u8> = pending.drain(..=newline).collect();
+        let line = String::from_utf8_lossy(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let value: Value = serde_json::from_str(line.trim())?;
+        if let Some(message) = value.get("error").and_then(Value::as_str) {
+            return Err(std::io::Error::other(message.to_string()));
+        }
+        if let Some(reason) = value.get("done_reason").and_then(Value::as_str) {
+            *done_reason.lock().unwrap() = Some(reason.to_string());
+        }
+        batch.push(serde_json::from_value(value)?);
+    }
+
+    Ok(batch)
+}
+
+// Function to send a generation request and stream the answer to the terminal
+pub async fn generate(ollama: &Ollama, local: bool, request: GenerationRequest<'_>, model: &str, request_id: &str, interactive: bool) -> Result<Metrics, Box<dyn std::error::Error>> {
+    generate_to(ollama, local, request, model, request_id, interactive, &Sink::Terminal).await
+}
+
+// Function to send a generation request for a server's client, which receives the text
+// through `sink`. Nobody is at the terminal to answer questions, so it never offers a pull.
+pub async fn generate_into(ollama: &Ollama, local: bool, request: GenerationRequest<'_>, model: &str, request_id: &str, sink: &Sink) -> Result<Metrics, Box<dyn std::error::Error>> {
+    generate_to(ollama, local, request, model, request_id, false, sink).await
+}
+
+// Function to send a generation request and stream the answer into `sink`, re-issuing the
+// request up to --retry-on-empty times when the model finishes without producing any text
+async fn generate_to(ollama: &Ollama, local: bool, request: GenerationRequest<'_>, model: &str, request_id: &str, interactive: bool, sink: &Sink) -> Result<Metrics, Box<dyn std::error::Error>> {
+    let retries = settings::get().retry_on_empty;
+    let mut attempt = 0;
+    warmup::note_used(local, model);
+    // The spinner is for the terminal; servers answer several clients at once
+    let spinner = matches!(sink, Sink::Terminal);
+
+    loop {
+        let start_time = Instant::now();
+        if spinner {
+            loading::start();
+        }
+        let stop_spinner = |_: &Box<dyn std::error::Error>| if spinner { loading::stop() };
+        let (stream, done_reason) = open_stream(ollama, local, &request, interactive).await.inspect_err(stop_spinner)?;
+        let mut metrics = stream_response(stream, model, request_id, start_time, sink).await.inspect_err(stop_spinner)?;
+        metrics.done_reason = done_reason.lock().unwrap().take();
+
+        let empty = metrics.response.trim().is_empty();
+        if !empty || attempt >= retries || metrics.stop_reason.is_some() {
+            if attempt > 0 && empty {
+                logging::info(&format!("{} Response still empty after {} retries", status::warn(), attempt));
+            } else if attempt > 0 {
+                logging::info(&format!("{} Got a response after {} retries", status::ok(), attempt));
+            }
+            return Ok(metrics);
+        }
+
+        attempt += 1;
+        logging::info(&format!("{} Empty response, retrying ({}/{})", status::retry(), attempt, retries));
+    }
+}
+
+// Function to start the response stream, re-sending failed requests up to --retries times
+// with exponential backoff. Permanent errors (e.g. a missing model) fail immediately.
+async fn open_stream(ollama: &Ollama, local: bool, request: &GenerationRequest<'_>, interactive: bool) -> Result<(ChunkStream, DoneReason), Box<dyn std::error::Error>> {
+    let retries = settings::get().retries;
+    let mut attempt = 0;
+
+    loop {
+        let error = match modelmanager::generate_stream_or_pull(ollama, local, request, interactive).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => e,
+        };
+
+        let class = error::classify(error.as_ref());
+        if class == ErrorClass::Permanent || attempt >= retries {
+            logging::debug(&format!("not retrying {:?} error after {} retries: {}", class, attempt, error));
+            return Err(error);
+        }
+
+        attempt += 1;
+        let delay = Duration::from_millis(500 * 2u64.pow((attempt - 1).min(6)));
+        logging::debug(&format!("retrying {:?} error: {}", class, error));
+        // Not Send; let it go before waiting, so servers can run this on spawned tasks
+        drop(error);
+        logging::info(&format!("{} Request failed, retrying in {:.1}s ({}/{})", status::retry(), delay.as_secs_f64(), attempt, retries));
+        tokio::time::sleep(delay).await;
+    }
+}
+
+// Function to record a batch's final (done) chunk into the metrics. ollama-rs yields batches:
+// one network read can hold several JSON lines, and the done chunk isn't necessarily the last
+// element. The whole batch is checked before its text is handled, so stopping early on an
+// earlier chunk can't lose the metrics. Returns true if the batch finished the response.
+fn record_batch(metrics: &mut Metrics, batch: &[GenerationResponse]) -> bool {
+    let done = batch.iter().find(|resp| resp.done);
+    if let Some(resp) = done {
+        metrics.record_final(resp);
+    }
+    done.is_some()
+}
+
+// Function to write a generation stream to its sink as it arrives and collect its metrics.
+// Takes ollama-rs's typed stream as well as a raw ChunkStream. The stop conditions (--stop-regex,
+// --abort-on-loop, --max-gen-time) apply to every sink; the display options only to the terminal.
+pub async fn stream_response<S, E>(mut stream: S, model: &str, request_id: &str, start_time: Instant, sink: &Sink) -> Result<Metrics, Box<dyn std::error::Error>>
+where
+    S: Stream<Item = Result<Vec<GenerationResponse>, E>> + Unpin,
+    E: std::error::Error + 'static,
+{
+    let terminal = matches!(sink, Sink::Terminal);
+    let mut stdout = io::BufWriter::new(io::stdout());
+    // Flushing every token keeps terminals responsive but slows down large piped outputs
+    let buffered = settings::get().buffered;
+    let mut unflushed = 0;
+    let mut last_flush = Instant::now();
+    let stream_json = terminal && settings::get().stream_json;
+    let is_terminal = terminal && std::io::stdout().is_terminal();
+    // Nothing goes to stdout when the answer is for a server's client
+    let quiet = !terminal || settings::get().quiet;
+
+    // With --pager the answer is collected and opened in the pager once it is complete
+    let mut paged = (settings::get().pager && is_terminal && !stream_json && !quiet).then(String::new);
+    if paged.is_some() {
+        logging::info("(generating; the response opens in your pager when it's complete)");
+    }
+
+    // Drop escape codes the model wrote itself before anything else sees the text (--strip-ansi)
+    let mut stripper = settings::get().strip_ansi.then(AnsiStripper::default);
+
+    // Line numbers in code blocks are for reading, so keep files and JSON output clean
+    let mut numberer = (settings::get().number_code && is_terminal && !stream_json)
+        .then(CodeNumberer::default);
+
+    // Only wrap for people reading in a terminal; piped and JSON output stay untouched
+    let mut wrapper = settings::get().wrap
+        .filter(|_| is_terminal && !stream_json)
+        .map(WordWrapper::new);
+
+    // Optional repetition watchdog (--detect-loops / --abort-on-loop)
+    let abort_on_loop = settings::get().abort_on_loop;
+    let mut loop_detector = (settings::get().detect_loops || abort_on_loop).then(LoopDetector::default);
+
+    // Client-side stop condition (--stop-regex); the pattern was validated at startup
+    let stop_regex = settings::get().stop_regex.as_deref().and_then(|pattern| Regex::new(pattern).ok());
+
+    // Running token count and speed on a status line (--live-metrics), started with the first data
+    let live_inline = is_terminal && paged.is_none() && !quiet && !stream_json;
+    let mut live = None;
+
+    // Wall-clock cap counted from the first token (--max-gen-time); the deadline is set once it arrives
+    let max_gen_time = settings::get().max_gen_time.map(Duration::from_secs);
+    let mut gen_deadline: Option<tokio::time::Instant> = None;
+
+    // Variables to track metrics
+    let mut response_text = String::new();
+    let mut metrics = Metrics::new(model);
+    metrics.request_id = request_id.to_string();
+    metrics.timestamp = logging::timestamp();
+    let mut chunks = 0;
+    let mut finished = false;
+
+    'stream: loop {
+        // Don't wait on a stalled stream past the --max-gen-time deadline
+        let next = match gen_deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, stream.next()).await {
+                Ok(next) => next,
+                Err(_) => None,
+            },
+            None => stream.next().await,
+        };
+        let Some(res) = next else {
+            break;
+        };
+
+        let responses = res?;
+        if record_batch(&mut metrics, &responses) {
+            finished = true;
+        }
+
+        // The first data means the model is loaded; clear "Loading model into memory..."
+        if terminal {
+            loading::stop();
+        }
+        if chunks == 0 {
+            live = LiveMetrics::new(live_inline);
+        }
+
+        for resp in responses {
+            chunks += 1;
+
+            let chunk = match stripper.as_mut() {
+                Some(stripper) => stripper.push(&resp.response),
+                None => resp.response.clone(),
+            };
+            if metrics.first_token_time == 0.0 && !chunk.is_empty() {
+                metrics.first_token_time = start_time.elapsed().as_secs_f64();
+                gen_deadline = max_gen_time.map(|limit| tokio::time::Instant::now() + limit);
+            }
+
+            // Write the response text (one JSON object per chunk in --stream-json mode)
+            let text = if stream_json {
+                if chunk.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}\n", delta_message(&chunk))
+                }
+            } else {
+                let text = match numberer.as_mut() {
+                    Some(numberer) => numberer.push(&chunk),
+                    None => chunk.clone(),
+                };
+                match wrapper.as_mut() {
+                    Some(wrapper) => wrapper.push(&text),
+                    None => text,
+                }
+            };
+            if let Some(live) = live.as_mut() {
+                live.clear();
+            }
+            if let Some(paged) = paged.as_mut() {
+                paged.push_str(&text);
+            } else if !quiet {
+                stdout.write_all(text.as_bytes()).await?;
+                unflushed += text.len();
+                if !buffered || unflushed >= FLUSH_BYTES || last_flush.elapsed() >= FLUSH_INTERVAL {
+                    stdout.flush().await?;
+                    unflushed = 0;
+                    last_flush = Instant::now();
+                }
+            }
+            if let Some(live) = live.as_mut().filter(|live| live.due()) {
+                stdout.flush().await?;
+                live.show(chunks);
+            }
+            // A client that has gone away can't read the rest, so stop generating it
+            if let Sink::Channel(sender) = sink {
+                if !chunk.is_empty() && sender.send(chunk.clone()).is_err() {
+                    metrics.stop_reason = Some("client disconnected".to_string());
+                    break 'stream;
+                }
+            }
+
+            // Collect response text for token counting
+            let checked = response_text.len();
+            response_text.push_str(&chunk);
+
+            if let Some(regex) = &stop_regex {
+                let mut start = checked.saturating_sub(STOP_REGEX_LOOKBACK);
+                while !response_text.is_char_boundary(start) {
+                    start -= 1;
+                }
+                if regex.is_match(&response_text[start..]) {
+                    metrics.stop_reason = Some("stopped on regex".to_string());
+                    break 'stream;
+                }
+            }
+
+            if let Some(detector) = loop_detector.as_mut() {
+                if let Some(phrase) = detector.push(&chunk) {
+                    stdout.flush().await?;
+                    logging::info(&format!("\n{} Model appears to be repeating itself: \"{}\"", status::warn(), phrase.trim()));
+                    if abort_on_loop {
+                        metrics.stop_reason = Some("repetition detected".to_string());
+                        break 'stream;
+                    }
+                    // Warn only once per response
+                    loop_detector = None;
+                }
+            }
+        }
+
+        if gen_deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline) {
+            break;
+        }
+    }
+
+    // Reaching the deadline stops the stream like --abort-on-loop does; what arrived is kept
+    if let (Some(limit), Some(deadline)) = (max_gen_time, gen_deadline) {
+        if !finished && metrics.stop_reason.is_none() && tokio::time::Instant::now() >= deadline {
+            metrics.stop_reason = Some(format!("stopped after {} seconds", limit.as_secs()));
+        }
+    }
+
+    if terminal {
+        loading::stop();
+    }
+    if let Some(live) = live.as_mut() {
+        live.clear();
+    }
+
+    let mut rest = match numberer.as_mut() {
+        Some(numberer) => numberer.finish(),
+        None => String::new(),
+    };
+    if let Some(wrapper) = wrapper.as_mut() {
+        rest = wrapper.push(&rest);
+        rest.push_str(&wrapper.finish());
+    }
+    if let Some(paged) = paged.as_mut() {
+        paged.push_str(&rest);
+    } else if !rest.is_empty() && !quiet {
+        stdout.write_all(rest.as_bytes()).await?;
+    }
+    stdout.flush().await?;
+
+    if let Some(paged) = paged {
+        pager::show(&paged);
+    }
+
+    metrics.finish(start_time.elapsed(), &response_text);
+
+    // Tell an empty answer apart from a stream that closed without sending anything
+    if response_text.is_empty() && metrics.stop_reason.is_none() {
+        let reason = match (chunks, finished) {
+            (0, _) => "the stream closed without sending any data",
+            (_, false) => "the stream closed before the model finished",
+            (_, true) => "the model finished without producing any text",
+        };
+        logging::info(&format!("{} Server returned an empty response: {}", status::warn(), reason));
+    }
+
+    logging::debug(&format!("request {} finished: {} tokens in {:.2}s", metrics.request_id, metrics.tokens, metrics.total_time));
+
+    if stream_json {
+        stdout.write_all(format!("{}\n", done_message(&metrics)).as_bytes()).await?;
+        stdout.flush().await?;
+    }
+
+    Ok(metrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(text: &str, done: bool, eval_count: Option<u64>) -> GenerationResponse {
+        serde_json::from_value(json!({
+            "model": "llama3.2",
+            "created_at": "2024-01-01T00:00:00Z",
+            "response": text,
+            "done": done,
+            "eval_count": eval_count,
+        })).unwrap()
+    }
+
+    #[test]
+    fn record_batch_finds_done_on_a_non_final_element() {
+        let batch = [chunk("Hel", false, None), chunk("lo", true, Some(42)), chunk("", false, None)];
+        let mut metrics = Metrics::new("llama3.2");
+
+        assert!(record_batch(&mut metrics, &batch));
+        assert_eq!(metrics.eval_count, 42);
+        assert!(metrics.final_response.is_some());
+    }
+
+    #[test]
+    fn record_batch_finds_done_on_the_last_element() {
+        let batch = [chunk("a", false, None), chunk("b", false, None), chunk("", true, Some(7))];
+        let mut metrics = Metrics::new("llama3.2");
+
+        assert!(record_batch(&mut metrics, &batch));
+        assert_eq!(metrics.eval_count, 7);
+    }
+
+    #[test]
+    fn parse_lines_keeps_done_reason_and_partial_lines() {
+        let done_reason = DoneReason::default();
+        let mut pending = concat!(
+            r#"{"model":"llama3.2","created_at":"2024-01-01T00:00:00Z","response":"Hi","done":false}"#, "\n",
+            r#"{"model":"llama3.2","created_at":"2024-01-01T00:00:00Z","response":"","done":true,"done_reason":"length","eval_count":2}"#, "\n",
+            r#"{"model":"#,
+        ).as_bytes().to_vec();
+
+        let batch = parse_lines(&mut pending, &done_reason).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch[0].response, "Hi");
+        assert!(batch[1].done);
+        assert_eq!(done_reason.lock().unwrap().as_deref(), Some("length"));
+        assert_eq!(pending, br#"{"model":"#);
+    }
+
+    #[test]
+    fn parse_lines_stops_on_an_error_line() {
+        let mut pending = b"{\"error\":\"model 'xyz' not found\"}\n".to_vec();
+        let error = parse_lines(&mut pending, &DoneReason::default()).unwrap_err();
+        assert_eq!(error.to_string(), "model 'xyz' not found");
+    }
+
+    #[test]
+    fn record_batch_without_done_leaves_the_metrics_alone() {
+        let batch = [chunk("a", false, None), chunk("b", false, None)];
+        let mut metrics = Metrics::new("llama3.2");
+
+        assert!(!record_batch(&mut metrics, &batch));
+        assert_eq!(metrics.eval_count, 0);
+        assert!(metrics.final_response.is_none());
+    }
+}
@@ -0,0 +1,124 @@
+use ollama_rs::Ollama;
+use ollama_rs::generation::completion::request::GenerationRequest;
+use std::fs;
+
+use crate::metrics::Metrics;
+use crate::{client, error, logging, options, prompt, settings, streaming, tokencount};
+
+// A chunk may fill half the context window; the rest is left for instructions and the summary
+const CHUNK_SHARE: u64 = 2;
+// Characters used to measure how much text fits in a token for this model
+const SAMPLE_CHARS: usize = 2000;
+// Stop merging summaries after this many rounds and summarize whatever is left
+const MAX_REDUCE_ROUNDS: usize = 3;
+
+const MAP_PROMPT: &str = "Summarize the following part of a longer document. Keep the key facts, names and numbers.";
+const REDUCE_PROMPT: &str = "The following are summaries of consecutive parts of one document. Combine them into a single coherent summary.";
+
+// Function to measure characters per token on a sample of the text (falls back to ~4)
+async fn chars_per_token(ollama: &Ollama, model: &str, text: &str) -> f64 {
+    let sample: String = text.chars().take(SAMPLE_CHARS).collect();
+
+    match tokencount::prompt_tokens(ollama, model, sample.clone()).await {
+        Ok(tokens) if tokens > 0 => sample.chars().count() as f64 / tokens as f64,
+        _ => 4.0,
+    }
+}
+
+// Function to split text into chunks of at most `max_chars`, preferring paragraph breaks
+fn split_chunks(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for paragraph in text.split("\n\n").filter(|p| !p.trim().is_empty()) {
+        if !current.is_empty() && current.chars().count() + paragraph.chars().count() + 2 > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+
+        // A single paragraph larger than a chunk is cut at character boundaries
+        let chars: Vec<char> = paragraph.chars().collect();
+        for piece in chars.chunks(max_chars) {
+            if current.chars().count() + piece.len() > max_chars {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str("\n\n");
+            }
+            current.extend(piece);
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+// Function to summarize one piece of text without streaming
+async fn summarize_chunk(ollama: &Ollama, model: &str, instruction: &str, text: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let request = options::apply(GenerationRequest::new(model.to_string(), format!("{}\n\n{}", instruction, text)), model);
+    let response = ollama.generate(request).await?;
+    Ok(response.response.trim().to_string())
+}
+
+// Function to summarize a large file with map-reduce: summarize each chunk (map), merge the
+// summaries until they fit in one request (reduce), then stream the final summary
+pub async fn summarize_file(path: String, local: bool) -> Result<Metrics, Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+
+    let text = fs::read_to_string(&path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let model = settings::model_for(local);
+    let (ollama, server_url) = client::build_client(local)?;
+    logging::info(&format!("Connecting to: {}", server_url));
+    logging::info(&format!("Using model: {}", model));
+
+    let num_ctx = prompt::context_window(&ollama, &model).await;
+    let ratio = chars_per_token(&ollama, &model, &text).await;
+    let chunk_chars = ((num_ctx / CHUNK_SHARE) as f64 * ratio).max(1.0) as usize;
+
+    let chunks = split_chunks(&text, chunk_chars);
+    logging::info(&format!("Split {} into {} chunk(s) for a {}-token context window", path, chunks.len(), num_ctx));
+
+    // Map: one summary per chunk (a short file is summarized directly below)
+    let mut summaries = if chunks.len() > 1 {
+        let mut summaries = Vec::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            logging::info(&format!("Summarizing chunk {}/{}...", index + 1, chunks.len()));
+            summaries.push(summarize_chunk(&ollama, &model, MAP_PROMPT, chunk).await
+                .map_err(|e| error::explain(e, &server_url))?);
+        }
+        summaries
+    } else {
+        chunks
+    };
+
+    // Reduce: merge groups of summaries until everything fits in a single request
+    let mut round = 0;
+    while summaries.join("\n\n").chars().count() > chunk_chars && round < MAX_REDUCE_ROUNDS {
+        round += 1;
+        let groups = split_chunks(&summaries.join("\n\n"), chunk_chars);
+        let mut merged = Vec::new();
+        for (index, group) in groups.iter().enumerate() {
+            logging::info(&format!("Combining summaries {}/{} (round {})...", index + 1, groups.len(), round));
+            merged.push(summarize_chunk(&ollama, &model, REDUCE_PROMPT, group).await
+                .map_err(|e| error::explain(e, &server_url))?);
+        }
+        summaries = merged;
+    }
+
+    let instruction = if summaries.len() > 1 || round > 0 { REDUCE_PROMPT } else { MAP_PROMPT };
+    let final_prompt = format!("{}\n\n{}", instruction, summaries.join("\n\n"));
+    let request_id = logging::start_request(&final_prompt);
+    let request = options::apply(GenerationRequest::new(model.clone(), final_prompt), &model);
+
+    logging::info("\n--- Summary ---");
+    let metrics = streaming::generate(&ollama, local, request, &model, &request_id, false).await
+        .map_err(|e| error::explain(e, &server_url))?;
+
+    match settings::get().metrics_format.as_deref() {
+        Some(format) => metrics.print(Some(format)),
+        None => logging::info(""),
+    }
+
+    Ok(metrics)
+}
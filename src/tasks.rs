@@ -0,0 +1,77 @@
+use std::future::Future;
+use std::sync::{Mutex, Once};
+use tokio::task::JoinHandle;
+
+use crate::{status, warmup};
+
+// Long-running work (servers, warm-ups) started from the menu. Each task runs on the
+// tokio runtime while the menu stays interactive, and is aborted when the program exits.
+struct BackgroundTask {
+    name: String,
+    handle: JoinHandle<()>,
+}
+
+static TASKS: Mutex<Vec<BackgroundTask>> = Mutex::new(Vec::new());
+
+// Function to run a task in the background; its error, if any, is printed when it ends
+pub fn spawn<F>(name: &str, task: F)
+where
+    F: Future<Output = Result<(), Box<dyn std::error::Error>>> + Send + 'static,
+{
+    let task_name = name.to_string();
+    let handle = tokio::spawn(async move {
+        if let Err(e) = task.await {
+            println!("\n{} Background task '{}' failed: {}", status::err(), task_name, e);
+        }
+    });
+
+    lock().push(BackgroundTask { name: name.to_string(), handle });
+}
+
+// Function to list the tasks that are still running (finished ones are forgotten)
+pub fn running() -> Vec<String> {
+    let mut tasks = lock();
+    tasks.retain(|task| !task.handle.is_finished());
+    tasks.iter().map(|task| task.name.clone()).collect()
+}
+
+// Function to abort every background task, e.g. on exit or Ctrl-C. Returns how many were stopped.
+pub fn shutdown() -> usize {
+    let tasks: Vec<BackgroundTask> = lock().drain(..).collect();
+    let mut stopped = 0;
+
+    for task in tasks {
+        if !task.handle.is_finished() {
+            task.handle.abort();
+            stopped += 1;
+        }
+    }
+
+    stopped
+}
+
+static CTRL_C_HANDLER: Once = Once::new();
+
+// Function to stop everything and leave when Ctrl-C is pressed. The menu reads stdin
+// synchronously, so this runs as its own task rather than inside the menu loop.
+// process::exit skips the normal cleanup, so --unload-on-exit is handled here too.
+// Only the first call installs the handler.
+pub fn exit_on_ctrl_c() {
+    CTRL_C_HANDLER.call_once(|| {
+        tokio::spawn(async {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                let stopped = shutdown();
+                if stopped > 0 {
+                    println!("\nStopped {} background task(s)", stopped);
+                }
+                warmup::unload_used_models().await;
+                println!("\n{} Goodbye!", status::bye());
+                std::process::exit(130);
+            }
+        });
+    });
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Vec<BackgroundTask>> {
+    TASKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
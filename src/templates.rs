@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Prompt templates are plain text files in ./templates/, e.g. templates/ocr.txt.
+// A few built-ins are always available; a file with the same name overrides one.
+const TEMPLATES_DIR: &str = "./templates";
+
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("describe", "Describe this image in detail."),
+    ("ocr", "Extract all text from this image as plain text."),
+    ("alt-text", "Write a concise alt text for this image, under 125 characters."),
+];
+
+// Function to collect every template by name, with files overriding the built-ins
+pub fn load_templates() -> Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
+    let mut templates: BTreeMap<String, String> = BUILTIN_TEMPLATES
+        .iter()
+        .map(|(name, text)| (name.to_string(), text.to_string()))
+        .collect();
+
+    let dir = Path::new(TEMPLATES_DIR);
+    if !dir.exists() {
+        return Ok(templates);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("txt") {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+            templates.insert(name.to_string(), fs::read_to_string(&path)?.trim().to_string());
+        }
+    }
+
+    Ok(templates)
+}
+
+// Function to look up a single template by name
+pub fn load_template(name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut templates = load_templates()?;
+    templates
+        .remove(name)
+        .ok_or_else(|| format!("Template '{}' not found (see --template-list)", name).into())
+}
+
+// Function to print the available templates for --template-list
+pub fn list_templates() -> Result<(), Box<dyn std::error::Error>> {
+    println!("Available templates (add your own as {}/<name>.txt):", TEMPLATES_DIR);
+    for (name, text) in load_templates()? {
+        println!("  - {}: {}", name, text.lines().next().unwrap_or(""));
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use ollama_rs::Ollama;
+use ollama_rs::generation::completion::request::GenerationRequest;
+use ollama_rs::models::ModelOptions;
+use serde_json::json;
+
+use crate::{client, prompt, settings};
+
+// Function to ask the server how many tokens a prompt uses.
+// num_predict = 0 makes the server evaluate the prompt and stop immediately.
+pub async fn prompt_tokens(ollama: &Ollama, model: &str, prompt: String) -> Result<u64, Box<dyn std::error::Error>> {
+    let request = GenerationRequest::new(model.to_string(), prompt)
+        .options(ModelOptions::default().num_predict(0));
+    let response = ollama.generate(request).await?;
+
+    let count = response
+        .prompt_eval_count
+        .ok_or("The server did not report a prompt token count")?;
+    Ok(count)
+}
+
+// Function to report how many tokens a prompt uses without generating a response
+pub async fn count_tokens(prompt: String, local: bool, as_json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+
+    let prompt = prompt::prepare(prompt);
+    let model = settings::model_for(local);
+    let (ollama, _) = client::build_client(local)?;
+
+    let count = prompt_tokens(&ollama, &model, prompt).await?;
+
+    if as_json {
+        println!("{}", json!({ "model": model, "prompt_tokens": count }));
+    } else {
+        println!("Prompt tokens: {} (model {})", count, model);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use ollama_rs::generation::chat::{ChatMessage, request::ChatMessageRequest};
+use ollama_rs::generation::tools::{ToolCall, ToolFunctionInfo, ToolInfo, ToolType};
+use schemars::json_schema;
+use serde_json::Value;
+
+use crate::{client, logging, prompt, settings, status};
+
+// Stop asking the model after this many tool round-trips so a confused model can't loop forever
+const MAX_TOOL_ROUNDS: usize = 5;
+
+// Function to describe the tools the model is allowed to call
+fn available_tools() -> Vec<ToolInfo> {
+    vec![
+        ToolInfo {
+            tool_type: ToolType::Function,
+            function: ToolFunctionInfo {
+                name: "calculator".to_string(),
+                description: "Evaluate an arithmetic expression using + - * / and parentheses".to_string(),
+                parameters: json_schema!({
+                    "type": "object",
+                    "properties": {
+                        "expression": {
+                            "type": "string",
+                            "description": "The expression to evaluate, e.g. (3 + 4) * 2"
+                        }
+                    },
+                    "required": ["expression"]
+                }),
+            },
+        },
+        ToolInfo {
+            tool_type: ToolType::Function,
+            function: ToolFunctionInfo {
+                name: "current_time".to_string(),
+                description: "Get the current date and time in UTC".to_string(),
+                parameters: json_schema!({
+                    "type": "object",
+                    "properties": {}
+                }),
+            },
+        },
+    ]
+}
+
+// Function to run a single tool call requested by the model and return its textual result
+fn execute_tool(call: &ToolCall) -> String {
+    match call.function.name.as_str() {
+        "calculator" => {
+            let expression = match call.function.arguments.get("expression").and_then(Value::as_str) {
+                Some(expression) => expression,
+                None => return "Error: missing 'expression' argument".to_string(),
+            };
+            match evaluate_expression(expression) {
+                Ok(value) => value.to_string(),
+                Err(e) => format!("Error: {}", e),
+            }
+        },
+        "current_time" => logging::timestamp(),
+        other => format!("Error: unknown tool '{}'", other),
+    }
+}
+
+// Function to evaluate a simple arithmetic expression (+ - * / and parentheses)
+fn evaluate_expression(expression: &str) -> Result<f64, String> {
+    let chars: Vec<char> = expression.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut pos = 0;
+    let value = parse_sum(&chars, &mut pos)?;
+
+    if pos != chars.len() {
+        return Err(format!("unexpected character '{}'", chars[pos]));
+    }
+    Ok(value)
+}
+
+fn parse_sum(chars: &[char], pos: &mut usize) -> Result<f64, String> {
+    let mut value = parse_product(chars, pos)?;
+    while *pos < chars.len() && (chars[*pos] == '+' || chars[*pos] == '-') {
+        let op = chars[*pos];
+        *pos += 1;
+        let rhs = parse_product(chars, pos)?;
+        if op == '+' { value += rhs } else { value -= rhs }
+    }
+    Ok(value)
+}
+
+fn parse_product(chars: &[char], pos: &mut usize) -> Result<f64, String> {
+    let mut value = parse_factor(chars, pos)?;
+    while *pos < chars.len() && (chars[*pos] == '*' || chars[*pos] == '/') {
+        let op = chars[*pos];
+        *pos += 1;
+        let rhs = parse_factor(chars, pos)?;
+        if op == '*' {
+            value *= rhs;
+        } else if rhs == 0.0 {
+            return Err("division by zero".to_string());
+        } else {
+            value /= rhs;
+        }
+    }
+    Ok(value)
+}
+
+fn parse_factor(chars: &[char], pos: &mut usize) -> Result<f64, String> {
+    match chars.get(*pos) {
+        Some('-') => {
+            *pos += 1;
+            Ok(-parse_factor(chars, pos)?)
+        },
+        Some('(') => {
+            *pos += 1;
+            let value = parse_sum(chars, pos)?;
+            if chars.get(*pos) != Some(&')') {
+                return Err("missing closing parenthesis".to_string());
+            }
+            *pos += 1;
+            Ok(value)
+        },
+        Some(c) if c.is_ascii_digit() || *c == '.' => {
+            let start = *pos;
+            while *pos < chars.len() && (chars[*pos].is_ascii_digit() || chars[*pos] == '.') {
+                *pos += 1;
+            }
+            let number: String = chars[start..*pos].iter().collect();
+            number.parse::<f64>().map_err(|_| format!("invalid number '{}'", number))
+        },
+        Some(c) => Err(format!("unexpected character '{}'", c)),
+        None => Err("unexpected end of expression".to_string()),
+    }
+}
+
+// Function to chat with the model while letting it call the local tools
+pub async fn run_tool_demo(prompt: String, local: bool) -> Result<(), Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+
+    let model = settings::model_for(local);
+
+    let (ollama, url) = client::build_client(local)?;
+    println!("Connecting to: {}", url);
+    println!("Using model: {}", model);
+    println!("Available tools: calculator, current_time");
+
+    let mut messages = vec![ChatMessage::user(prompt::prepare(prompt))];
+
+    for _ in 0..MAX_TOOL_ROUNDS {
+        let request = ChatMessageRequest::new(model.clone(), messages.clone())
+            .tools(available_tools());
+        let response = ollama.send_chat_messages(request).await?;
+
+        let tool_calls = response.message.tool_calls.clone();
+        messages.push(response.message.clone());
+
+        // No tool calls means the model has produced its final answer
+        if tool_calls.is_empty() {
+            println!("\n--- Response ---");
+            println!("{}", response.message.content);
+            return Ok(());
+        }
+
+        for call in tool_calls {
+            println!("{} Tool call: {}({})", status::tool(), call.function.name, call.function.arguments);
+            let result = execute_tool(&call);
+            println!("   {} {}", status::result(), result);
+            messages.push(ChatMessage::tool(result));
+        }
+    }
+
+    Err(format!("Model did not produce an answer after {} tool rounds", MAX_TOOL_ROUNDS).into())
+}
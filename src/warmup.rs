@@ -0,0 +1,90 @@
+use ollama_rs::generation::completion::request::GenerationRequest;
+use ollama_rs::generation::parameters::KeepAlive;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::{client, metrics, options, settings, status};
+
+// Models this run has sent requests to, as (local, model), so --unload-on-exit frees
+// exactly the models that were used, e.g. the vision model after an image run
+static USED_MODELS: Mutex<Vec<(bool, String)>> = Mutex::new(Vec::new());
+
+// Function to remember that a model was sent a request and may now be in memory
+pub fn note_used(local: bool, model: &str) {
+    let mut used = USED_MODELS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !used.iter().any(|(used_local, used_model)| *used_local == local && used_model == model) {
+        used.push((local, model.to_string()));
+    }
+}
+
+// Function to preload a model into memory and report how long loading took
+pub async fn warmup_model(local: bool) -> Result<(), Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+
+    let model = settings::model_for(local);
+
+    let (ollama, url) = client::build_client(local)?;
+    println!("Warming up model {} on {}", model, url);
+    note_used(local, &model);
+
+    // An empty prompt makes the server load the model without generating anything
+    let keep_alive = options::keep_alive();
+    let pinned = matches!(keep_alive, Some(KeepAlive::Indefinitely));
+    let mut request = GenerationRequest::new(model.clone(), String::new());
+    if let Some(keep_alive) = keep_alive {
+        request = request.keep_alive(keep_alive);
+    }
+
+    let start_time = Instant::now();
+    let response = ollama.generate(request).await?;
+    let elapsed = start_time.elapsed();
+
+    // The server only reports a load duration once the model is actually in memory
+    let Some(load_duration) = response.load_duration else {
+        return Err(format!("Server did not confirm that model {} was loaded", model).into());
+    };
+
+    if load_duration > 0 {
+        let load_secs = metrics::nanos_to_secs(load_duration);
+        println!("{} Model {} loaded in {:.2}s (request took {:.2}s)", status::ok(), model, load_secs, elapsed.as_secs_f64());
+    } else {
+        println!("{} Model {} is ready (request took {:.2}s)", status::ok(), model, elapsed.as_secs_f64());
+    }
+
+    if pinned {
+        println!("{} Model {} loaded and pinned in memory.", status::ok(), model);
+    }
+
+    Ok(())
+}
+
+// Function to ask the server to unload a model now (keep_alive = 0)
+async fn unload_model(local: bool, model: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (ollama, url) = client::build_client(local)?;
+
+    let request = GenerationRequest::new(model.to_string(), String::new())
+        .keep_alive(KeepAlive::UnloadOnCompletion);
+    ollama.generate(request).await?;
+
+    println!("{} Unloaded model {} on {}", status::ok(), model, url);
+    Ok(())
+}
+
+// Function to handle --unload-on-exit: unload every model this run used, and nothing when
+// no request was sent. A failure must not turn a successful run into an error, so it is
+// only reported.
+pub async fn unload_used_models() {
+    if !settings::get().unload_on_exit {
+        return;
+    }
+
+    let used: Vec<(bool, String)> = USED_MODELS.lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .drain(..)
+        .collect();
+    for (local, model) in used {
+        if let Err(e) = unload_model(local, &model).await {
+            println!("{} Could not unload model {}: {}", status::warn(), model, e);
+        }
+    }
+}
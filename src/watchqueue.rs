@@ -0,0 +1,134 @@
+use notify::{EventKind, RecursiveMode, Watcher};
+use ollama_rs::Ollama;
+use ollama_rs::generation::completion::request::GenerationRequest;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{client, logging, options, prompt, settings, status};
+
+// Files with these extensions are prompts; answers are written as <name>.response.md
+const PROMPT_EXTENSIONS: &[&str] = &["txt", "prompt"];
+
+// A file counts as completely written once its size stops changing for this long
+const STABLE_INTERVAL: Duration = Duration::from_millis(500);
+
+// An empty file is given this many intervals to receive its content before it is skipped
+const EMPTY_CHECKS: u32 = 20;
+
+// Function to tell whether a path is a prompt file waiting in the queue
+fn is_prompt_file(path: &Path) -> bool {
+    path.is_file() && path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| PROMPT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+// Function to wait until a file that may still be being written has a stable, non-zero size
+async fn wait_until_stable(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut previous = fs::metadata(path)?.len();
+    let mut empty_checks = 0;
+    loop {
+        tokio::time::sleep(STABLE_INTERVAL).await;
+        let current = fs::metadata(path)?.len();
+        if current == previous && current > 0 {
+            return Ok(());
+        }
+        if current == 0 {
+            empty_checks += 1;
+            if empty_checks >= EMPTY_CHECKS {
+                return Err("the prompt file is empty".into());
+            }
+        }
+        previous = current;
+    }
+}
+
+// Function to move a processed prompt into a subfolder (done/ or failed/), keeping its name
+fn move_into(path: &Path, folder: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(folder)?;
+    let file_name = path.file_name().unwrap_or_default();
+    fs::rename(path, folder.join(file_name))
+}
+
+// Function to answer one prompt file: the answer goes next to it, the prompt into done/
+async fn process(ollama: &Ollama, model: &str, path: &Path, queue: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    wait_until_stable(path).await?;
+
+    let text = fs::read_to_string(path)?;
+    let request = options::apply(GenerationRequest::new(model.to_string(), prompt::prepare(text)), model);
+    let response = ollama.generate(request).await?;
+
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("prompt");
+    let output = queue.join(format!("{}.response.md", stem));
+    fs::write(&output, &response.response)?;
+    move_into(path, &queue.join("done"))?;
+    Ok(output)
+}
+
+// Function to handle --watch-queue: answer every prompt file dropped into a directory, one
+// at a time, until Ctrl-C. Files already there when it starts are answered first. A prompt
+// that fails is moved to failed/ so it isn't retried forever.
+pub async fn watch(dir: String, local: bool) -> Result<(), Box<dyn std::error::Error>> {
+    settings::load_dotenv();
+
+    let queue = PathBuf::from(&dir);
+    if !queue.is_dir() {
+        return Err(format!("--watch-queue expects a directory, but {} is not one", dir).into());
+    }
+
+    let model = settings::model_for(local);
+    let (ollama, url) = client::build_client(local)?;
+
+    // notify calls back on its own thread; hand the paths over to the async loop
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                for path in event.paths {
+                    let _ = sender.send(path);
+                }
+            }
+        }
+    })?;
+    watcher.watch(&queue, RecursiveMode::NonRecursive)?;
+
+    println!("{} Watching {} for prompt files ({}) on {} with {}", status::ok(), dir, PROMPT_EXTENSIONS.join(", "), url, model);
+    println!("Answers are written as <name>.response.md; press Ctrl-C to stop.");
+
+    let mut pending: Vec<PathBuf> = fs::read_dir(&queue)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_prompt_file(path))
+        .collect();
+    // Reversed, so pop() hands them out in name order
+    pending.sort_by(|a, b| b.cmp(a));
+
+    loop {
+        let path = match pending.pop() {
+            Some(path) => path,
+            None => match receiver.recv().await {
+                Some(path) => path,
+                None => return Ok(()),
+            },
+        };
+
+        // One file raises several events; anything already moved away is skipped
+        if !is_prompt_file(&path) {
+            continue;
+        }
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        logging::info(&format!("Processing {}", name));
+        match process(&ollama, &model, &path, &queue).await {
+            Ok(output) => println!("{} {} -> {}", status::ok(), name, output.display()),
+            Err(e) => {
+                println!("{} {}: {}", status::err(), name, e);
+                if path.exists() {
+                    if let Err(e) = move_into(&path, &queue.join("failed")) {
+                        println!("{} Could not move {} to failed/: {}", status::warn(), name, e);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+// Soft-wraps streamed text at word boundaries. Text arrives in arbitrary chunks,
+// so the current word is held back until we know whether it still fits on the line.
+pub struct WordWrapper {
+    width: usize,
+    column: usize,
+    word: String,
+    // True right after we inserted a line break, so the space that caused it is dropped
+    soft_wrapped: bool,
+}
+
+impl WordWrapper {
+    pub fn new(width: usize) -> Self {
+        WordWrapper {
+            width: width.max(1),
+            column: 0,
+            word: String::new(),
+            soft_wrapped: false,
+        }
+    }
+
+    // Function to feed a chunk of streamed text and get back what can be printed now
+    pub fn push(&mut self, chunk: &str) -> String {
+        let mut output = String::new();
+
+        for c in chunk.chars() {
+            match c {
+                '\n' => {
+                    self.flush_word(&mut output);
+                    output.push('\n');
+                    self.column = 0;
+                    self.soft_wrapped = false;
+                },
+                ' ' | '\t' => {
+                    self.flush_word(&mut output);
+                    if self.column >= self.width {
+                        output.push('\n');
+                        self.column = 0;
+                        self.soft_wrapped = true;
+                    } else if !(self.column == 0 && self.soft_wrapped) {
+                        // Keep indentation after real newlines, drop spaces after soft wraps
+                        output.push(c);
+                        self.column += 1;
+                    }
+                },
+                _ => self.word.push(c),
+            }
+        }
+
+        output
+    }
+
+    // Function to emit whatever word is still buffered at the end of the stream
+    pub fn finish(&mut self) -> String {
+        let mut output = String::new();
+        self.flush_word(&mut output);
+        output
+    }
+
+    fn flush_word(&mut self, output: &mut String) {
+        if self.word.is_empty() {
+            return;
+        }
+
+        let word_len = self.word.chars().count();
+        // Words longer than the width are printed as-is on their own line
+        if self.column > 0 && self.column + word_len > self.width {
+            output.push('\n');
+            self.column = 0;
+        }
+
+        output.push_str(&self.word);
+        self.column += word_len;
+        self.word.clear();
+        self.soft_wrapped = false;
+    }
+}
@@ -0,0 +1,75 @@
+use futures_util::{SinkExt, StreamExt};
+use ollama_rs::generation::completion::request::GenerationRequest;
+use serde_json::json;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::streaming::Sink;
+use crate::{client, error, logging, options, prompt, settings, status, streaming};
+
+// Errors inside connection tasks must be Send to cross tokio::spawn
+type ConnectionError = Box<dyn std::error::Error + Send + Sync>;
+
+// Function to serve generations over websockets: each text message is a prompt, answered
+// with the same JSON messages as --stream-json (deltas, then a final metrics message)
+pub async fn serve(addr: String, local: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(&addr).await?;
+    println!("{} Websocket server listening on ws://{}", status::ok(), addr);
+    println!("Using model: {}", settings::model_for(local));
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        logging::debug(&format!("websocket client connected: {}", peer));
+
+        // One task per client so a slow or broken client doesn't block the others
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, local).await {
+                println!("{} Websocket client {}: {}", status::warn(), peer, e);
+            }
+        });
+    }
+}
+
+// Function to answer every prompt a single client sends until it disconnects. Each answer
+// goes through the same generation path as the terminal (retries, stop conditions), with the
+// text handed over a channel and forwarded to the socket as it arrives.
+async fn handle_connection(stream: TcpStream, local: bool) -> Result<(), ConnectionError> {
+    let mut socket = tokio_tungstenite::accept_async(stream).await?;
+    let (ollama, server) = client::build_client(local).map_err(|e| e.to_string())?;
+    let model = settings::model_for(local);
+
+    while let Some(message) = socket.next().await {
+        let user_prompt = match message? {
+            Message::Text(text) => prompt::prepare(text),
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let request_id = logging::start_request(&user_prompt);
+        let request = options::apply(GenerationRequest::new(model.clone(), user_prompt), &model);
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let generation = async {
+            let sink = Sink::Channel(sender);
+            streaming::generate_into(&ollama, local, request, &model, &request_id, &sink).await
+                .map_err(|e| error::explain(e, &server).to_string())
+        };
+        let forward = async {
+            while let Some(text) = receiver.recv().await {
+                socket.send(Message::Text(streaming::delta_message(&text))).await?;
+            }
+            Ok::<_, ConnectionError>(())
+        };
+        let (result, forwarded) = tokio::join!(generation, forward);
+        forwarded?;
+
+        let message = match result {
+            Ok(metrics) => streaming::done_message(&metrics),
+            Err(e) => json!({ "error": e }).to_string(),
+        };
+        socket.send(Message::Text(message)).await?;
+    }
+
+    Ok(())
+}